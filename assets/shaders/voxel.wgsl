@@ -0,0 +1,122 @@
+#import bevy_pbr::mesh_functions::{get_world_from_local, mesh_position_local_to_clip}
+
+@group(2) @binding(0) var atlas_texture: texture_2d<f32>;
+@group(2) @binding(1) var atlas_sampler: sampler;
+
+// Must match ATLAS_COLUMNS in mesher.rs
+const ATLAS_COLUMNS: u32 = 3u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) @interpolate(flat) tile: u32,
+};
+
+#ifdef PACKED_VERTICES
+// See PackedVertex in packed.rs for the bit layout
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) packed: u32,
+};
+
+fn bits(value: u32, shift: u32, count: u32) -> u32 {
+    return (value >> shift) & ((1u << count) - 1u);
+}
+
+// Must match brightness in light.rs
+fn brightness(level: u32) -> f32 {
+    return pow(0.8, f32(15u - level));
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    // Direction::ALL order
+    var normals = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 0.0, -1.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, -1.0, 0.0),
+    );
+    // Must match AO_CURVE in mesher.rs
+    var ao_curve = array<f32, 4>(0.45, 0.6, 0.8, 1.0);
+
+    let position = vec3<f32>(
+        f32(bits(vertex.packed, 0u, 6u)),
+        f32(bits(vertex.packed, 6u, 6u)),
+        f32(bits(vertex.packed, 12u, 6u)),
+    );
+    let face = bits(vertex.packed, 18u, 3u);
+    let ao = bits(vertex.packed, 21u, 2u);
+    let light = bits(vertex.packed, 23u, 4u);
+    let texture = bits(vertex.packed, 27u, 5u);
+
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(
+        get_world_from_local(vertex.instance_index),
+        vec4<f32>(position, 1.0),
+    );
+    out.normal = normals[face];
+    // Same as texture_uv in mesher.rs, only the fraction matters
+    if face < 2u {
+        out.uv = vec2<f32>(position.z, -position.y);
+    } else if face < 4u {
+        out.uv = vec2<f32>(position.x, -position.y);
+    } else {
+        out.uv = position.xz;
+    }
+    let shade = brightness(light) * ao_curve[ao];
+    out.color = vec4<f32>(shade, shade, shade, 1.0);
+    // Top, side and bottom columns, like texture_tile
+    var column = 1u;
+    if face == 4u {
+        column = 0u;
+    } else if face == 5u {
+        column = 2u;
+    }
+    out.tile = texture * ATLAS_COLUMNS + column;
+    return out;
+}
+#else
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) color: vec4<f32>,
+    @location(4) tile: u32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(
+        get_world_from_local(vertex.instance_index),
+        vec4<f32>(vertex.position, 1.0),
+    );
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    out.normal = vertex.normal;
+    out.tile = vertex.tile;
+    return out;
+}
+#endif
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    // UVs count voxels, wrap them inside the tile so merged quads repeat the texture
+    let size = vec2<f32>(textureDimensions(atlas_texture));
+    let tiles = vec2<f32>(f32(ATLAS_COLUMNS), size.y * f32(ATLAS_COLUMNS) / size.x);
+    let tile = vec2<f32>(f32(in.tile % ATLAS_COLUMNS), f32(in.tile / ATLAS_COLUMNS));
+    let uv = (tile + fract(in.uv)) / tiles;
+    // No mipmaps, and fract breaks the derivatives at tile edges
+    let texel = textureSampleLevel(atlas_texture, atlas_sampler, uv, 0.0);
+
+    // Light and AO are baked into the vertex colors, the sides are shaded a
+    // bit so faces stay readable
+    let shade = 0.8 + 0.2 * in.normal.y - 0.1 * abs(in.normal.x);
+    return vec4<f32>(texel.rgb * in.color.rgb * shade, texel.a * in.color.a);
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_cubes::chunk::*;
+use bevy_cubes::mesher::MeshingMode;
+use bevy_cubes::world::VoxelWorld;
+
+const ITERATIONS: u32 = 5;
+// Binary has to stay well ahead of Naive. On a single core VM:
+//   Naive: 2.18ms/chunk, Greedy: 3.31ms/chunk, Binary: 222µs/chunk
+// Binary is measured ~10x faster, the margin leaves room for noise.
+const MIN_BINARY_SPEEDUP: u32 = 5;
+
+fn main() {
+    let mut world = VoxelWorld::new();
+    for x in 0..4 {
+        for y in 0..3 {
+            for z in 0..4 {
+                let pos = IVec3::new(x, y, z);
+                world.add_chunk(pos, gen_chunk_flat(pos));
+            }
+        }
+    }
+
+    let mut times = Vec::new();
+    for mode in [MeshingMode::Naive, MeshingMode::Greedy, MeshingMode::Binary] {
+        let mut elapsed = Duration::ZERO;
+        let mut quads = 0;
+        for _ in 0..ITERATIONS {
+            quads = 0;
+            let start = Instant::now();
+            for chunk in world.chunks.values() {
+                quads += chunk.build_mesh(&world, mode).quad_count();
+            }
+            elapsed += start.elapsed();
+        }
+        let per_chunk = elapsed / (ITERATIONS * world.chunks.len() as u32);
+        println!("{:?}: {:?}/chunk, {} quads", mode, per_chunk, quads);
+        times.push(per_chunk);
+    }
+
+    let (naive, binary) = (times[0], times[2]);
+    assert!(
+        binary * MIN_BINARY_SPEEDUP <= naive,
+        "binary meshing ({:?}) isn't {}x faster than naive ({:?})",
+        binary,
+        MIN_BINARY_SPEEDUP,
+        naive,
+    );
+}
@@ -0,0 +1,225 @@
+// Headless world generation and meshing benchmark, no window or GPU.
+//
+//   cargo run --release --bin meshbench -- --size 8x4x8 --generator caves --mesher binary
+//
+// Prints one JSON object so runs can be compared across commits.
+
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_cubes::chunk::ChunkNeighbours;
+use bevy_cubes::generator::{GeneratorConfig, GeneratorKind};
+use bevy_cubes::light::light_chunk;
+use bevy_cubes::mesher::{mesh_transparent, MeshingMode};
+use bevy_cubes::world::VoxelWorld;
+
+struct Options {
+    size: IVec3,
+    // Chunk position of the region's lowest corner
+    min: IVec3,
+    kind: GeneratorKind,
+    mode: MeshingMode,
+    seed: Option<u64>,
+    features: bool,
+}
+
+const USAGE: &str = "usage: meshbench [--size NxMxK] [--min X,Y,Z] \
+[--generator noise|heightmap|biomes|caves] [--mesher naive|greedy|binary] \
+[--seed N] [--no-features]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    exit(2);
+}
+
+fn parse_vec(value: &str, separator: char) -> Option<IVec3> {
+    let parts: Vec<i32> = value
+        .split(separator)
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [x, y, z] => Some(IVec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        size: IVec3::new(8, 4, 8),
+        min: IVec3::new(0, -2, 0),
+        kind: GeneratorKind::default(),
+        mode: MeshingMode::Binary,
+        seed: None,
+        features: true,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--no-features" {
+            options.features = false;
+            continue;
+        }
+        if arg == "--help" || arg == "-h" {
+            println!("{}", USAGE);
+            exit(0);
+        }
+        let Some(value) = args.next() else {
+            fail(&format!("missing value for {}", arg));
+        };
+        match arg.as_str() {
+            "--size" => {
+                options.size = parse_vec(&value, 'x')
+                    .filter(|size| size.min_element() > 0)
+                    .unwrap_or_else(|| fail(&format!("bad size {}", value)));
+            }
+            "--min" => {
+                options.min =
+                    parse_vec(&value, ',').unwrap_or_else(|| fail(&format!("bad min {}", value)));
+            }
+            "--generator" => {
+                options.kind = match value.as_str() {
+                    "noise" => GeneratorKind::Noise,
+                    "heightmap" => GeneratorKind::Heightmap,
+                    "biomes" => GeneratorKind::Biomes,
+                    "caves" => GeneratorKind::Caves,
+                    _ => fail(&format!("unknown generator {}", value)),
+                };
+            }
+            "--mesher" => {
+                options.mode = match value.as_str() {
+                    "naive" => MeshingMode::Naive,
+                    "greedy" => MeshingMode::Greedy,
+                    "binary" => MeshingMode::Binary,
+                    _ => fail(&format!("unknown mesher {}", value)),
+                };
+            }
+            "--seed" => {
+                options.seed = Some(
+                    value
+                        .parse()
+                        .unwrap_or_else(|_| fail(&format!("bad seed {}", value))),
+                );
+            }
+            _ => fail(&format!("unknown argument {}", arg)),
+        }
+    }
+    options
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+fn main() {
+    let options = parse_args();
+    let mut config = GeneratorConfig {
+        kind: options.kind,
+        features: options.features,
+        ..default()
+    };
+    if let Some(seed) = options.seed {
+        config.seed = seed;
+    }
+    let generator = config.build();
+
+    let mut positions = Vec::new();
+    for x in 0..options.size.x {
+        for y in 0..options.size.y {
+            for z in 0..options.size.z {
+                positions.push(options.min + IVec3::new(x, y, z));
+            }
+        }
+    }
+
+    // Same steps as the streaming plugin, run on one thread
+    let mut generate = Duration::ZERO;
+    let mut light = Duration::ZERO;
+    let mut insert = Duration::ZERO;
+    let mut world = VoxelWorld::new();
+    for pos in &positions {
+        let start = Instant::now();
+        let (mut chunk, overflow) = generator.generate_with_overflow(*pos);
+        generate += start.elapsed();
+
+        let start = Instant::now();
+        let changed = world.apply_feature_writes(&mut chunk);
+        insert += start.elapsed();
+
+        let start = Instant::now();
+        light_chunk(&mut chunk);
+        light += start.elapsed();
+
+        let start = Instant::now();
+        world.add_chunk(*pos, chunk);
+        world.fresh.insert(*pos);
+        if changed {
+            world.unsaved.insert(*pos);
+        }
+        insert += start.elapsed();
+
+        let start = Instant::now();
+        world.stitch_light(*pos);
+        light += start.elapsed();
+
+        let start = Instant::now();
+        world.queue_feature_writes(*pos, overflow);
+        insert += start.elapsed();
+    }
+
+    let mut mesh = Duration::ZERO;
+    let (mut quads, mut vertices, mut indices) = (0, 0, 0);
+    let (mut bytes, mut packed_bytes) = (0, 0);
+    for pos in &positions {
+        let chunk = world.get_chunk(*pos).unwrap();
+        let start = Instant::now();
+        let neighbours = ChunkNeighbours::new(&world, *pos);
+        let opaque = chunk.build_mesh_with_neighbours(&neighbours, options.mode, false);
+        let transparent = mesh_transparent(&chunk, &neighbours, false);
+        mesh += start.elapsed();
+        for buffers in [&opaque, &transparent] {
+            quads += buffers.quad_count();
+            vertices += buffers.positions.len();
+            indices += buffers.indices.len();
+            bytes += buffers.memory_usage();
+        }
+        // Untimed, only for the size of the packed format
+        let opaque = chunk.build_mesh_with_neighbours(&neighbours, options.mode, true);
+        let transparent = mesh_transparent(&chunk, &neighbours, true);
+        packed_bytes += opaque.memory_usage() + transparent.memory_usage();
+    }
+
+    let total = generate + light + insert + mesh;
+    let chunks = positions.len();
+    println!("{{");
+    println!("  \"generator\": \"{:?}\",", options.kind);
+    println!("  \"mesher\": \"{:?}\",", options.mode);
+    println!("  \"seed\": {},", config.seed);
+    println!("  \"features\": {},", options.features);
+    println!(
+        "  \"size\": [{}, {}, {}],",
+        options.size.x, options.size.y, options.size.z
+    );
+    println!("  \"chunks\": {},", chunks);
+    println!(
+        "  \"chunks_per_sec\": {:.2},",
+        chunks as f64 / total.as_secs_f64()
+    );
+    println!(
+        "  \"mesh_chunks_per_sec\": {:.2},",
+        chunks as f64 / mesh.as_secs_f64()
+    );
+    println!("  \"quads\": {},", quads);
+    println!("  \"vertices\": {},", vertices);
+    println!("  \"indices\": {},", indices);
+    println!("  \"mesh_bytes\": {},", bytes);
+    println!("  \"packed_mesh_bytes\": {},", packed_bytes);
+    println!("  \"voxel_bytes\": {},", world.memory_usage());
+    println!("  \"timings_ms\": {{");
+    println!("    \"generate\": {:.3},", ms(generate));
+    println!("    \"light\": {:.3},", ms(light));
+    println!("    \"insert\": {:.3},", ms(insert));
+    println!("    \"mesh\": {:.3},", ms(mesh));
+    println!("    \"total\": {:.3}", ms(total));
+    println!("  }}");
+    println!("}}");
+}
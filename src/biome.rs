@@ -0,0 +1,221 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::generator::{GeneratorConfig, WorldGenerator};
+use crate::light::ChunkLight;
+
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Plains,
+    Desert,
+    Mountains,
+    Tundra,
+}
+
+pub struct BiomeProfile {
+    // Position on the temperature/humidity map, both in -1..1
+    pub temperature: f32,
+    pub humidity: f32,
+    pub surface: BlockId,
+    pub subsurface: BlockId,
+    // Terrain height is base_height + amplitude * fbm, fbm in 0..1
+    pub base_height: f32,
+    pub amplitude: f32,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Desert, Biome::Mountains, Biome::Tundra];
+
+    pub fn profile(self) -> BiomeProfile {
+        match self {
+            Biome::Plains => BiomeProfile {
+                temperature: 0.2,
+                humidity: 0.3,
+                surface: BlockId::Grass,
+                subsurface: BlockId::Dirt,
+                base_height: 4.,
+                amplitude: 24.,
+            },
+            Biome::Desert => BiomeProfile {
+                temperature: 0.8,
+                humidity: -0.6,
+                surface: BlockId::Sand,
+                subsurface: BlockId::Sand,
+                base_height: 2.,
+                amplitude: 12.,
+            },
+            Biome::Mountains => BiomeProfile {
+                temperature: -0.1,
+                humidity: -0.2,
+                surface: BlockId::Stone,
+                subsurface: BlockId::Stone,
+                base_height: 24.,
+                amplitude: 128.,
+            },
+            Biome::Tundra => BiomeProfile {
+                temperature: -0.8,
+                humidity: 0.4,
+                surface: BlockId::Snow,
+                subsurface: BlockId::Dirt,
+                base_height: 8.,
+                amplitude: 32.,
+            },
+        }
+    }
+}
+
+// Height and surface of one terrain column
+#[derive(Clone, Copy, Debug)]
+pub struct Column {
+    pub height: f32,
+    pub biome: Biome,
+}
+
+// fBm heightmap with domain warping, biomes picked from temperature/humidity
+// noise and heights blended across biome borders
+pub struct BiomeGenerator {
+    config: GeneratorConfig,
+    height_noise: FastNoise,
+    warp_noise: FastNoise,
+    temperature_noise: FastNoise,
+    humidity_noise: FastNoise,
+}
+
+// Unit frequency fBm, callers scale their sample coordinates
+pub fn noise(seed: u64, noise_type: NoiseType, octaves: i32) -> FastNoise {
+    let mut noise = FastNoise::seeded(seed);
+    noise.set_noise_type(noise_type);
+    noise.set_fractal_type(FractalType::FBM);
+    noise.set_fractal_octaves(octaves);
+    noise.set_frequency(1.);
+    noise
+}
+
+impl BiomeGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        let seed = config.seed;
+        BiomeGenerator {
+            height_noise: noise(seed, NoiseType::PerlinFractal, config.fbm_octaves),
+            warp_noise: noise(seed.wrapping_add(1), NoiseType::Perlin, 1),
+            temperature_noise: noise(seed.wrapping_add(2), NoiseType::PerlinFractal, 2),
+            humidity_noise: noise(seed.wrapping_add(3), NoiseType::PerlinFractal, 2),
+            config,
+        }
+    }
+
+    pub fn column(&self, x: i32, z: i32) -> Column {
+        let config = &self.config;
+        let (x, z) = (x as f32, z as f32);
+
+        let warp_x = self
+            .warp_noise
+            .get_noise(x / config.warp_scale, z / config.warp_scale);
+        let warp_z = self
+            .warp_noise
+            .get_noise(x / config.warp_scale + 100., z / config.warp_scale + 100.);
+        let wx = x + warp_x * config.warp_strength;
+        let wz = z + warp_z * config.warp_strength;
+
+        let temperature = self
+            .temperature_noise
+            .get_noise(wx / config.biome_scale, wz / config.biome_scale)
+            .clamp(-1., 1.);
+        let humidity = self
+            .humidity_noise
+            .get_noise(wx / config.biome_scale + 50., wz / config.biome_scale + 50.)
+            .clamp(-1., 1.);
+        let fbm = ((self
+            .height_noise
+            .get_noise(wx / config.hill_scale, wz / config.hill_scale)
+            + 1.)
+            / 2.)
+            .clamp(0., 1.);
+
+        // Inverse distance weights on the biome map, smooth across borders
+        let mut height = 0.;
+        let mut total = 0.;
+        let mut biome = Biome::Plains;
+        let mut best = 0.;
+        for candidate in Biome::ALL {
+            let profile = candidate.profile();
+            let distance = Vec2::new(
+                profile.temperature - temperature,
+                profile.humidity - humidity,
+            )
+            .length_squared();
+            let weight = 1. / (distance + 0.01).powi(2);
+            height += weight * (profile.base_height + profile.amplitude * fbm);
+            total += weight;
+            if weight > best {
+                best = weight;
+                biome = candidate;
+            }
+        }
+
+        Column {
+            height: height / total,
+            biome,
+        }
+    }
+}
+
+impl BiomeGenerator {
+    // Fills `data` with the terrain of a chunk, returns the column heights
+    pub fn fill_terrain(
+        &self,
+        chunk_pos: IVec3,
+        data: &mut [[[BlockId; 32]; 32]; 32],
+    ) -> [[f32; 32]; 32] {
+        let mut heights = [[0.; 32]; 32];
+        for (x, plane) in data.iter_mut().enumerate() {
+            for z in 0..32usize {
+                let column = self.column(
+                    chunk_pos.x * CHUNK_SIZE + x as i32,
+                    chunk_pos.z * CHUNK_SIZE + z as i32,
+                );
+                heights[x][z] = column.height;
+                let profile = column.biome.profile();
+                let sea_level = self.config.sea_level;
+                let surface = if column.height > self.config.snow_line as f32 {
+                    BlockId::Snow
+                } else if column.height < (sea_level + 2) as f32 {
+                    // Beaches and sea floor
+                    BlockId::Sand
+                } else {
+                    profile.surface
+                };
+
+                for (y, row) in plane.iter_mut().enumerate() {
+                    let world_y = y as i32 + chunk_pos.y * CHUNK_SIZE;
+                    let depth = column.height - world_y as f32;
+                    row[z] = if depth <= 0. && world_y < sea_level {
+                        BlockId::Water
+                    } else if depth <= 0. {
+                        BlockId::Air
+                    } else if depth <= 1. {
+                        surface
+                    } else if depth <= 4. {
+                        profile.subsurface
+                    } else {
+                        BlockId::Stone
+                    };
+                }
+            }
+        }
+        heights
+    }
+}
+
+impl WorldGenerator for BiomeGenerator {
+    fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        let mut data = [[[BlockId::Air; 32]; 32]; 32];
+        self.fill_terrain(chunk_pos, &mut data);
+        Chunk {
+            data: ChunkData::from_array(&data, chunk_pos),
+            position: chunk_pos,
+            light: ChunkLight::default(),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum BlockId {
+    #[default]
+    Air,
+    Stone,
+    Dirt,
+    Grass,
+    Sand,
+    Water,
+    Snow,
+    Log,
+    Leaves,
+    Lamp,
+}
+
+pub struct BlockProperties {
+    pub name: &'static str,
+    // Takes part in meshing and collision
+    pub solid: bool,
+    // Neighbouring faces are not culled against it
+    pub transparent: bool,
+    pub color: [f32; 4],
+    // Row in the block texture atlas, see plugins/textures.rs
+    pub texture: u16,
+    // Block light emitted, 0..=15
+    pub light: u8,
+}
+
+pub const BLOCK_COUNT: usize = 10;
+
+// Indexed by `BlockId as usize`, keep in the same order as the enum
+pub const BLOCKS: [BlockProperties; BLOCK_COUNT] = [
+    BlockProperties {
+        name: "air",
+        solid: false,
+        transparent: true,
+        color: [0.0, 0.0, 0.0, 0.0],
+        texture: 0,
+        light: 0,
+    },
+    BlockProperties {
+        name: "stone",
+        solid: true,
+        transparent: false,
+        color: [0.5, 0.5, 0.5, 1.0],
+        texture: 1,
+        light: 0,
+    },
+    BlockProperties {
+        name: "dirt",
+        solid: true,
+        transparent: false,
+        color: [0.45, 0.3, 0.15, 1.0],
+        texture: 2,
+        light: 0,
+    },
+    BlockProperties {
+        name: "grass",
+        solid: true,
+        transparent: false,
+        color: [0.3, 0.65, 0.2, 1.0],
+        texture: 3,
+        light: 0,
+    },
+    BlockProperties {
+        name: "sand",
+        solid: true,
+        transparent: false,
+        color: [0.85, 0.8, 0.55, 1.0],
+        texture: 4,
+        light: 0,
+    },
+    BlockProperties {
+        name: "water",
+        solid: false,
+        transparent: true,
+        color: [0.15, 0.35, 0.8, 0.6],
+        texture: 5,
+        light: 0,
+    },
+    BlockProperties {
+        name: "snow",
+        solid: true,
+        transparent: false,
+        color: [0.95, 0.95, 0.98, 1.0],
+        texture: 6,
+        light: 0,
+    },
+    BlockProperties {
+        name: "log",
+        solid: true,
+        transparent: false,
+        color: [0.4, 0.27, 0.13, 1.0],
+        texture: 7,
+        light: 0,
+    },
+    BlockProperties {
+        name: "leaves",
+        solid: true,
+        transparent: false,
+        color: [0.15, 0.45, 0.12, 1.0],
+        texture: 8,
+        light: 0,
+    },
+    BlockProperties {
+        name: "lamp",
+        solid: true,
+        transparent: false,
+        color: [1.0, 0.85, 0.5, 1.0],
+        texture: 9,
+        light: 15,
+    },
+];
+
+impl BlockId {
+    pub const ALL: [BlockId; BLOCK_COUNT] = [
+        BlockId::Air,
+        BlockId::Stone,
+        BlockId::Dirt,
+        BlockId::Grass,
+        BlockId::Sand,
+        BlockId::Water,
+        BlockId::Snow,
+        BlockId::Log,
+        BlockId::Leaves,
+        BlockId::Lamp,
+    ];
+
+    pub fn from_u8(id: u8) -> Option<BlockId> {
+        BlockId::ALL.get(id as usize).copied()
+    }
+    pub fn properties(self) -> &'static BlockProperties {
+        &BLOCKS[self as usize]
+    }
+    pub fn name(self) -> &'static str {
+        self.properties().name
+    }
+    pub fn is_solid(self) -> bool {
+        self.properties().solid
+    }
+    pub fn is_transparent(self) -> bool {
+        self.properties().transparent
+    }
+    pub fn color(self) -> [f32; 4] {
+        self.properties().color
+    }
+    pub fn texture(self) -> u16 {
+        self.properties().texture
+    }
+    pub fn light(self) -> u8 {
+        self.properties().light
+    }
+}
@@ -0,0 +1,95 @@
+use crate::biome::{noise, BiomeGenerator};
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::generator::{GeneratorConfig, WorldGenerator};
+use crate::light::ChunkLight;
+
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+
+// Biome heightmap for the surface, carved underneath by 3D noise:
+// "cheese" caves where one noise field is high and "worm" tunnels where
+// two noise fields are both close to zero. Above the surface a third field
+// adds rock that thins out with height, leaving overhangs and arches.
+pub struct CaveGenerator {
+    config: GeneratorConfig,
+    terrain: BiomeGenerator,
+    cheese_noise: FastNoise,
+    worm_noise_a: FastNoise,
+    worm_noise_b: FastNoise,
+    overhang_noise: FastNoise,
+}
+
+impl CaveGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        let seed = config.seed;
+        CaveGenerator {
+            terrain: BiomeGenerator::new(config.clone()),
+            cheese_noise: noise(seed.wrapping_add(10), NoiseType::PerlinFractal, 2),
+            worm_noise_a: noise(seed.wrapping_add(11), NoiseType::PerlinFractal, 1),
+            worm_noise_b: noise(seed.wrapping_add(12), NoiseType::PerlinFractal, 1),
+            overhang_noise: noise(seed.wrapping_add(13), NoiseType::PerlinFractal, 2),
+            config,
+        }
+    }
+
+    pub fn is_cave(&self, pos: IVec3) -> bool {
+        let density = self.config.cave_density.clamp(0., 1.);
+        if density == 0. {
+            return false;
+        }
+        let scale = self.config.cave_scale;
+        let (x, y, z) = (pos.x as f32 / scale, pos.y as f32 / scale, pos.z as f32 / scale);
+
+        // Squashed vertically so caves are wider than they are tall
+        let cheese = self.cheese_noise.get_noise3d(x, y * 2., z);
+        if cheese > 0.6 - 0.4 * density {
+            return true;
+        }
+        let width = 0.02 + 0.06 * density;
+        self.worm_noise_a.get_noise3d(x, y, z).abs() < width
+            && self.worm_noise_b.get_noise3d(x, y, z).abs() < width
+    }
+
+    // `height` is how far `pos` is above the surface
+    pub fn is_overhang(&self, pos: IVec3, height: f32) -> bool {
+        let density = self.config.cave_density.clamp(0., 1.);
+        let max_height = self.config.overhang_height;
+        if density == 0. || height <= 0. || height >= max_height {
+            return false;
+        }
+        let scale = self.config.cave_scale;
+        let (x, y, z) = (pos.x as f32 / scale, pos.y as f32 / scale, pos.z as f32 / scale);
+        // Denser close to the surface, nothing at max_height
+        let threshold = 0.4 - 0.3 * density + 0.3 * height / max_height;
+        self.overhang_noise.get_noise3d(x, y * 3., z) > threshold
+    }
+}
+
+impl WorldGenerator for CaveGenerator {
+    fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        let mut data = [[[BlockId::Air; 32]; 32]; 32];
+        let heights = self.terrain.fill_terrain(chunk_pos, &mut data);
+
+        for (x, plane) in data.iter_mut().enumerate() {
+            for z in 0..32usize {
+                for (y, row) in plane.iter_mut().enumerate() {
+                    let pos = chunk_pos * CHUNK_SIZE + IVec3::new(x as i32, y as i32, z as i32);
+                    let depth = heights[x][z] - pos.y as f32;
+                    if row[z].is_solid() {
+                        if depth >= self.config.cave_min_depth && self.is_cave(pos) {
+                            row[z] = BlockId::Air;
+                        }
+                    } else if row[z] == BlockId::Air && self.is_overhang(pos, -depth) {
+                        row[z] = BlockId::Stone;
+                    }
+                }
+            }
+        }
+        Chunk {
+            data: ChunkData::from_array(&data, chunk_pos),
+            position: chunk_pos,
+            light: ChunkLight::default(),
+        }
+    }
+}
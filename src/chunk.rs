@@ -1,15 +1,14 @@
-use crate::quad::{new_quad, Direction};
+use crate::block::BlockId;
+use crate::generator::{HeightmapGenerator, NoiseGenerator, WorldGenerator};
+use crate::light::ChunkLight;
+use crate::mesher::*;
+use crate::palette::BlockStorage;
+use crate::quad::Direction;
 use crate::tools::ToUsize;
-use crate::world::VoxelWorld;
+use crate::world::{world_to_chunk, VoxelWorld};
 
-use bevy::math::f32::Vec3;
 use bevy::prelude::*;
-use bevy::render::{
-    mesh::Indices,
-    render_asset::RenderAssetUsages,
-    render_resource::PrimitiveTopology,
-};
-use bracket_noise::prelude::*;
+use bevy::render::mesh::Indices;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
@@ -18,183 +17,316 @@ pub static QUAD_COUNT: AtomicUsize = AtomicUsize::new(0);
 pub const CHUNK_SIZE: i32 = 32;
 pub const SEED: u64 = 1111;
 
-#[derive(Deref)]
+#[derive(Clone)]
 pub struct ChunkData {
-    #[deref]
-    data: [[[bool; 32]; 32]; 32],
+    blocks: BlockStorage,
     pub pos: IVec3,
 }
 
 impl ChunkData {
-    pub fn get<T>(&self, x: T, y: T, z: T) -> bool
+    pub fn new(pos: IVec3) -> Self {
+        ChunkData {
+            blocks: BlockStorage::default(),
+            pos,
+        }
+    }
+    pub fn from_array(data: &[[[BlockId; 32]; 32]; 32], pos: IVec3) -> Self {
+        let blocks = data.iter().flatten().flatten().copied();
+        ChunkData {
+            blocks: BlockStorage::from_blocks(blocks),
+            pos,
+        }
+    }
+    pub fn get<T>(&self, x: T, y: T, z: T) -> BlockId
     where
         T: ToUsize,
     {
-        self.data[x.to_usize()][y.to_usize()][z.to_usize()]
+        self.blocks
+            .get(BlockStorage::index(x.to_usize(), y.to_usize(), z.to_usize()))
+    }
+    pub fn set<T>(&mut self, x: T, y: T, z: T, block: BlockId)
+    where
+        T: ToUsize,
+    {
+        self.blocks.set(
+            BlockStorage::index(x.to_usize(), y.to_usize(), z.to_usize()),
+            block,
+        );
+    }
+    // Columns along z where each test holds, bit z of [x][y]
+    pub fn column_masks<const N: usize>(
+        &self,
+        tests: [fn(BlockId) -> bool; N],
+    ) -> [[[u32; 32]; 32]; N] {
+        self.blocks.column_masks(tests)
+    }
+    // The column along z at x, y where `test` holds, bit z
+    pub fn column_mask<T>(&self, x: T, y: T, test: impl Fn(BlockId) -> bool) -> u32
+    where
+        T: ToUsize,
+    {
+        self.blocks
+            .column_mask(BlockStorage::index(x.to_usize(), y.to_usize(), 0), test)
+    }
+    // Some(block) if the whole chunk is that block
+    pub fn single_block(&self) -> Option<BlockId> {
+        self.blocks.single_block()
+    }
+    pub fn compact(&mut self) {
+        self.blocks.compact();
+    }
+    pub fn memory_usage(&self) -> usize {
+        self.blocks.memory_usage()
     }
 }
 
+#[derive(Clone)]
 pub struct Chunk {
     pub position: IVec3,
     pub data: ChunkData,
+    // Not saved, relit with light::light_chunk when loaded
+    pub light: ChunkLight,
 }
 
+// All 26 chunks around a chunk, edge and corner ones are needed for AO
 #[derive(Deref)]
 pub struct ChunkNeighbours {
-    chunks: [Option<Arc<Chunk>>; 6],
+    // Indexed by neighbour_index, the middle entry is always None
+    chunks: [Option<Arc<Chunk>>; 27],
+}
+
+fn neighbour_index(offset: IVec3) -> Option<usize> {
+    if offset.abs().max_element() > 1 {
+        return None;
+    }
+    let index = offset + IVec3::ONE;
+    Some((index.x * 9 + index.y * 3 + index.z) as usize)
 }
 
 impl ChunkNeighbours {
     pub fn new(voxel_world: &VoxelWorld, middle_chunk: IVec3) -> Self {
-        let x = voxel_world.get_chunk(middle_chunk + IVec3::X);
-        let neg_x = voxel_world.get_chunk(middle_chunk - IVec3::X);
-        let y = voxel_world.get_chunk(middle_chunk + IVec3::Y);
-        let neg_y = voxel_world.get_chunk(middle_chunk - IVec3::Y);
-        let z = voxel_world.get_chunk(middle_chunk + IVec3::Z);
-        let neg_z = voxel_world.get_chunk(middle_chunk - IVec3::Z);
-
-        ChunkNeighbours {
-            chunks: [x, neg_x, y, neg_y, z, neg_z],
+        let mut chunks: [Option<Arc<Chunk>>; 27] = Default::default();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let offset = IVec3::new(x, y, z);
+                    if offset != IVec3::ZERO {
+                        chunks[neighbour_index(offset).unwrap()] =
+                            voxel_world.get_chunk(middle_chunk + offset);
+                    }
+                }
+            }
+        }
+        ChunkNeighbours { chunks }
+    }
+    // Treats a neighbour as not loaded, so faces against it aren't culled
+    pub fn clear(&mut self, offset: IVec3) {
+        if let Some(index) = neighbour_index(offset) {
+            self.chunks[index] = None;
         }
     }
     pub fn get(&self, pos:IVec3) -> &Option<Arc<Chunk>> {
-        match pos {
-           IVec3::X => &self[0],
-           IVec3::NEG_X => &self[1],
-           IVec3::Y => &self[2],
-           IVec3::NEG_Y => &self[3],
-           IVec3::Z => &self[4],
-           IVec3::NEG_Z => &self[5],
-            _ => &None
+        match neighbour_index(pos) {
+            Some(index) => &self[index],
+            None => &None,
         }
     }
-}
-
-impl Chunk {
-    pub fn gen_mesh(&self, world_data: &VoxelWorld) -> Mesh {
-        let mut vertices: Vec<[f32; 3]> = Vec::new();
-        let mut norm: Vec<Vec3> = Vec::new();
-        let neighbours = ChunkNeighbours::new(world_data,self.position);
-
-        for x in 0..32i32 {
-            for y in 0..32i32 {
-                for z in 0..32i32 {
-                    if !self.data.get(x, y, z) {
-                        continue;
-                    }
-                    for dir in world_data.get_voxel_neighbours(&self.data,&neighbours,IVec3::new(x, y, z)) {
-                        vertices.extend(new_quad(
-                            dir.clone(),
-                            Vec3::new(
-                                (self.position.x * CHUNK_SIZE + x) as f32,
-                                (self.position.y * CHUNK_SIZE + y) as f32,
-                                (self.position.z * CHUNK_SIZE + z) as f32,
-                            ),
-                        ));
-                        // Quad num ++
-                        QUAD_COUNT.fetch_add(1, Ordering::SeqCst);
-                        let normal = match dir {
-                            Direction::North => Vec3::X,
-                            Direction::South => Vec3::NEG_X,
-                            Direction::West => Vec3::Z,
-                            Direction::East => Vec3::NEG_Z,
-                            Direction::Up => Vec3::Y,
-                            Direction::Down => Vec3::NEG_Y,
-                        };
-                        norm.extend(vec![normal; 4]);
-                    }
+    // Block at a position relative to `chunk`, which may be inside one of the
+    // neighbours. None if that neighbour isn't loaded.
+    pub fn get_block(&self, chunk: &Chunk, pos: IVec3) -> Option<BlockId> {
+        let (chunk_offset, local) = world_to_chunk(pos);
+        if chunk_offset == IVec3::ZERO {
+            return Some(chunk.data.get(local.x, local.y, local.z));
+        }
+        self.get(chunk_offset)
+            .as_ref()
+            .map(|neighbour| neighbour.data.get(local.x, local.y, local.z))
+    }
+    // Light level at a position relative to `chunk`, see get_block
+    pub fn get_light(&self, chunk: &Chunk, pos: IVec3) -> Option<u8> {
+        let (chunk_offset, local) = world_to_chunk(pos);
+        if chunk_offset == IVec3::ZERO {
+            return Some(chunk.light.level(local));
+        }
+        self.get(chunk_offset)
+            .as_ref()
+            .map(|neighbour| neighbour.light.level(local))
+    }
+    //TODO Simplyfy (put middle chunkl in ChunkNeighbours and impl get_block(x,y,z) for ChunkNeighbours
+    pub fn get_voxel_neighbours(&self, chunk_data: &ChunkData, voxel_pos: IVec3) -> Vec<Direction> {
+        let mut directions: Vec<Direction> = Vec::new();
+        if voxel_pos.x == 0 {
+            if let Some(chunk) = self.get(IVec3::NEG_X) {
+                if chunk
+                    .data
+                    .get(31, voxel_pos.y, voxel_pos.z)
+                    .is_transparent()
+                {
+                    directions.push(Direction::South);
+                }
+            } else {
+                directions.push(Direction::South);
+            }
+        } else if voxel_pos.x == CHUNK_SIZE - 1 {
+            if let Some(chunk) = self.get(IVec3::X) {
+                if chunk.data.get(0, voxel_pos.y, voxel_pos.z).is_transparent() {
+                    directions.push(Direction::North);
                 }
+            } else {
+                directions.push(Direction::North);
             }
         }
-        let indeces = gen_indeces(vertices.len());
-
-        Mesh::new(
-            //TIANGLES
-            PrimitiveTopology::TriangleList,
-            //WIREFRAME
-            //PrimitiveTopology::LineList,
-            RenderAssetUsages::RENDER_WORLD,
-        )
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
-        .with_inserted_indices(indeces)
-        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, norm)
-    }
-}
-
-pub fn gen_chunk(chunk_pos: IVec3) -> Chunk {
-    let mut noise = FastNoise::new();
-    noise.set_seed(SEED);
-    noise.set_noise_type(NoiseType::Perlin);
-    noise.set_frequency(6.);
+        if voxel_pos.x != 0
+            && chunk_data
+                .get(voxel_pos.x - 1, voxel_pos.y, voxel_pos.z)
+                .is_transparent()
+        {
+            directions.push(Direction::South)
+        }
+        if voxel_pos.x != CHUNK_SIZE - 1
+            && chunk_data
+                .get(voxel_pos.x + 1, voxel_pos.y, voxel_pos.z)
+                .is_transparent()
+        {
+            directions.push(Direction::North)
+        }
 
-    let mut data = [[[false; 32]; 32]; 32];
+        if voxel_pos.y == 0 {
+            if let Some(chunk) = self.get(IVec3::NEG_Y) {
+                if chunk
+                    .data
+                    .get(voxel_pos.x, 31, voxel_pos.z)
+                    .is_transparent()
+                {
+                    directions.push(Direction::Down)
+                }
+            } else {
+                //directions.push(Direction::Down) //Remove world floor
+            }
+        } else if voxel_pos.y == CHUNK_SIZE - 1 {
+            if let Some(chunk) = self.get(IVec3::Y) {
+                if chunk.data.get(voxel_pos.x, 0, voxel_pos.z).is_transparent() {
+                    directions.push(Direction::Up)
+                }
+            } else {
+                directions.push(Direction::Up)
+            }
+        }
+        if voxel_pos.y != 0
+            && chunk_data
+                .get(voxel_pos.x, voxel_pos.y - 1, voxel_pos.z)
+                .is_transparent()
+        {
+            directions.push(Direction::Down)
+        }
+        if voxel_pos.y != CHUNK_SIZE - 1
+            && chunk_data
+                .get(voxel_pos.x, voxel_pos.y + 1, voxel_pos.z)
+                .is_transparent()
+        {
+            directions.push(Direction::Up)
+        }
 
-    for x in 0..32usize {
-        for y in 0..32usize {
-            for z in 0..32usize {
-                let n = noise.get_noise3d(
-                    ((chunk_pos.x * CHUNK_SIZE + x as i32) as f32) / 100.,
-                    ((chunk_pos.y * CHUNK_SIZE + y as i32) as f32) / 100.,
-                    ((chunk_pos.z * CHUNK_SIZE + z as i32) as f32) / 100.,
-                );
-                if n < 0. {
-                    data[x][y][z] = false;
-                } else {
-                    data[x][y][z] = true;
+        if voxel_pos.z == 0 {
+            if let Some(chunk) = self.get(IVec3::NEG_Z) {
+                if chunk
+                    .data
+                    .get(voxel_pos.x, voxel_pos.y, 31)
+                    .is_transparent()
+                {
+                    directions.push(Direction::East)
                 }
+            } else {
+                directions.push(Direction::East)
+            }
+        } else if voxel_pos.z == CHUNK_SIZE - 1 {
+            if let Some(chunk) = self.get(IVec3::Z) {
+                if chunk.data.get(voxel_pos.x, voxel_pos.y, 0).is_transparent() {
+                    directions.push(Direction::West)
+                }
+            } else {
+                directions.push(Direction::West)
             }
         }
-    }
+        if voxel_pos.z != 0
+            && chunk_data
+                .get(voxel_pos.x, voxel_pos.y, voxel_pos.z - 1)
+                .is_transparent()
+        {
+            directions.push(Direction::East)
+        }
+        if voxel_pos.z != CHUNK_SIZE - 1
+            && chunk_data
+                .get(voxel_pos.x, voxel_pos.y, voxel_pos.z + 1)
+                .is_transparent()
+        {
+            directions.push(Direction::West)
+        }
 
-    Chunk {
-        data: ChunkData {
-            data: data,
-            pos: chunk_pos,
-        },
-        position: chunk_pos,
+        directions
     }
 }
-pub fn gen_chunk_flat(chunk_pos: IVec3) -> Chunk {
-    let mut noise = FastNoise::new();
-    noise.set_seed(SEED);
-    noise.set_noise_type(NoiseType::Perlin);
-    noise.set_frequency(6.);
-
-    let mut data = [[[false; 32]; 32]; 32];
-
-    for x in 0..32usize {
-        for z in 0..32usize {
-            let mut n = (noise.get_noise(
-                ((chunk_pos.x * CHUNK_SIZE + x as i32) as f32) / 200.,
-                ((chunk_pos.z * CHUNK_SIZE + z as i32) as f32) / 200.,
-            ) + 1.)
-                * 16.;
-
-            n += (noise.get_noise(
-                ((chunk_pos.x * CHUNK_SIZE + x as i32) as f32) / 1000.,
-                ((chunk_pos.z * CHUNK_SIZE + z as i32) as f32) / 1000.,
-            ) + 1.)
-                * 16.
-                * 4.;
-            n -= 32.;
 
-            for y in 0..32usize {
-                //TODO Change this line
-                if ((y as i32 + chunk_pos.y * 32) as f32) < n {
-                    data[x][y][z] = true;
-                } else {
-                    data[x][y][z] = false;
-                }
-            }
+impl Chunk {
+    pub fn new(position: IVec3) -> Self {
+        Chunk {
+            position,
+            data: ChunkData::new(position),
+            light: ChunkLight::default(),
         }
     }
-    Chunk {
-        data: ChunkData {
-            data: data,
-            pos: chunk_pos,
-        },
-        position: chunk_pos,
+    pub fn gen_mesh(&self, world_data: &VoxelWorld, mode: MeshingMode) -> Mesh {
+        let neighbours = ChunkNeighbours::new(world_data, self.position);
+        self.gen_mesh_with_neighbours(&neighbours, mode)
     }
+    // Doesn't borrow the world, so it can run off the main thread
+    pub fn gen_mesh_with_neighbours(
+        &self,
+        neighbours: &ChunkNeighbours,
+        mode: MeshingMode,
+    ) -> Mesh {
+        let buffers = self.build_mesh_with_neighbours(neighbours, mode, false);
+        // Quad num ++
+        QUAD_COUNT.fetch_add(buffers.quad_count(), Ordering::SeqCst);
+        buffers.into_mesh()
+    }
+    // Opaque mesh plus the alpha blended pass for water, if there is any
+    pub fn gen_meshes_with_neighbours(
+        &self,
+        neighbours: &ChunkNeighbours,
+        mode: MeshingMode,
+        packed: bool,
+    ) -> ChunkMeshes {
+        ChunkMeshes::new(
+            self.build_mesh_with_neighbours(neighbours, mode, packed),
+            mesh_transparent(self, neighbours, packed),
+        )
+    }
+    pub fn build_mesh(&self, world_data: &VoxelWorld, mode: MeshingMode) -> MeshBuffers {
+        let neighbours = ChunkNeighbours::new(world_data, self.position);
+        self.build_mesh_with_neighbours(&neighbours, mode, false)
+    }
+    // `packed` fills one u32 per vertex instead of the full attributes
+    pub fn build_mesh_with_neighbours(
+        &self,
+        neighbours: &ChunkNeighbours,
+        mode: MeshingMode,
+        packed: bool,
+    ) -> MeshBuffers {
+        match mode {
+            MeshingMode::Naive => mesh_naive(self, neighbours, packed),
+            MeshingMode::Greedy => mesh_greedy(self, neighbours, packed),
+            MeshingMode::Binary => mesh_binary(self, neighbours, packed),
+        }
+    }
+}
+
+// Shorthands for the generators with the default config
+pub fn gen_chunk(chunk_pos: IVec3) -> Chunk {
+    NoiseGenerator::default().generate(chunk_pos)
+}
+pub fn gen_chunk_flat(chunk_pos: IVec3) -> Chunk {
+    HeightmapGenerator::default().generate(chunk_pos)
 }
 pub fn gen_indeces(vert_len: usize) -> Indices {
     let mut indices: Vec<u32> = Vec::new();
@@ -0,0 +1,160 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::generator::{GeneratorConfig, WorldGenerator};
+use crate::world::world_to_chunk;
+
+use bevy::prelude::*;
+use std::sync::Arc;
+
+// A block placed by a feature, in world coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureWrite {
+    pub pos: IVec3,
+    pub block: BlockId,
+}
+
+impl FeatureWrite {
+    // Features only grow into air, and logs through their own leaves
+    pub fn applies_to(&self, current: BlockId) -> bool {
+        current == BlockId::Air || (current == BlockId::Leaves && self.block == BlockId::Log)
+    }
+}
+
+// Writes the blocks that apply, true if any did
+pub fn apply_writes(chunk: &mut Chunk, writes: &[FeatureWrite]) -> bool {
+    let mut written = false;
+    for write in writes {
+        let (_, local) = world_to_chunk(write.pos);
+        if write.applies_to(chunk.data.get(local.x, local.y, local.z)) {
+            chunk.data.set(local.x, local.y, local.z, write.block);
+            written = true;
+        }
+    }
+    written
+}
+
+// Places trees and boulders on top of another generator's terrain. Every
+// feature is rooted in the chunk that contains its base and is derived from
+// the seed and the root column only, so it's the same whatever order chunks
+// are generated in. Blocks that fall outside of the root chunk are returned
+// from generate_with_overflow to be queued for the chunk they belong to.
+pub struct FeatureGenerator {
+    terrain: Arc<dyn WorldGenerator>,
+    config: GeneratorConfig,
+}
+
+// splitmix64 over the seed and a position
+fn hash(seed: u64, x: i32, z: i32, salt: u64) -> u64 {
+    let mut h = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ salt.wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+// Uniform in 0..1
+fn random(seed: u64, x: i32, z: i32, salt: u64) -> f32 {
+    (hash(seed, x, z, salt) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+impl FeatureGenerator {
+    pub fn new(terrain: Arc<dyn WorldGenerator>, config: GeneratorConfig) -> Self {
+        FeatureGenerator { terrain, config }
+    }
+
+    fn tree(&self, root: IVec3, writes: &mut Vec<FeatureWrite>) {
+        let seed = self.config.seed;
+        let trunk = 10 + (hash(seed, root.x, root.z, 2) % 8) as i32;
+        let radius = 4 + (hash(seed, root.x, root.z, 3) % 3) as i32;
+        let top = root + IVec3::Y * trunk;
+
+        for y in 1..=trunk {
+            writes.push(FeatureWrite {
+                pos: root + IVec3::Y * y,
+                block: BlockId::Log,
+            });
+        }
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let offset = IVec3::new(x, y, z);
+                    // Ragged edge so canopies aren't perfect spheres
+                    let jitter = random(seed, top.x + x, top.z + z, 4u64.wrapping_add(y as u64)) * 2.;
+                    if (offset.length_squared() as f32) <= (radius * radius) as f32 - jitter {
+                        writes.push(FeatureWrite {
+                            pos: top + offset,
+                            block: BlockId::Leaves,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn boulder(&self, root: IVec3, writes: &mut Vec<FeatureWrite>) {
+        let radius = 2 + (hash(self.config.seed, root.x, root.z, 6) % 2) as i32;
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let offset = IVec3::new(x, y, z);
+                    if offset.length_squared() <= radius * radius {
+                        writes.push(FeatureWrite {
+                            pos: root + IVec3::Y + offset,
+                            block: BlockId::Stone,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl WorldGenerator for FeatureGenerator {
+    // Parts of features in other chunks are dropped, use generate_with_overflow
+    fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        self.generate_with_overflow(chunk_pos).0
+    }
+
+    fn generate_with_overflow(&self, chunk_pos: IVec3) -> (Chunk, Vec<FeatureWrite>) {
+        let mut chunk = self.terrain.generate(chunk_pos);
+        let seed = self.config.seed;
+        let mut writes = Vec::new();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                // Surface block with air above it, both inside this chunk
+                let Some(y) = (0..CHUNK_SIZE - 1).rev().find(|y| {
+                    chunk.data.get(x, *y, z).is_solid()
+                        && chunk.data.get(x, y + 1, z) == BlockId::Air
+                }) else {
+                    continue;
+                };
+                let surface = chunk.data.get(x, y, z);
+                let root = chunk_pos * CHUNK_SIZE + IVec3::new(x, y, z);
+                let roll = random(seed, root.x, root.z, 1);
+                match surface {
+                    BlockId::Grass if roll < self.config.tree_chance => self.tree(root, &mut writes),
+                    BlockId::Stone | BlockId::Snow | BlockId::Sand
+                        if roll < self.config.boulder_chance =>
+                    {
+                        self.boulder(root, &mut writes)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut overflow = Vec::new();
+        for write in writes {
+            let (write_chunk, local) = world_to_chunk(write.pos);
+            if write_chunk != chunk_pos {
+                overflow.push(write);
+            } else if write.applies_to(chunk.data.get(local.x, local.y, local.z)) {
+                chunk.data.set(local.x, local.y, local.z, write.block);
+            }
+        }
+        (chunk, overflow)
+    }
+}
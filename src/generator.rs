@@ -0,0 +1,284 @@
+use crate::biome::BiomeGenerator;
+use crate::block::BlockId;
+use crate::caves::CaveGenerator;
+use crate::chunk::*;
+use crate::features::{FeatureGenerator, FeatureWrite};
+use crate::light::ChunkLight;
+
+use bevy::prelude::*;
+use bracket_noise::prelude::*;
+use std::sync::Arc;
+
+pub trait WorldGenerator: Send + Sync {
+    fn generate(&self, chunk_pos: IVec3) -> Chunk;
+    // Also returns blocks of features that reach into other chunks
+    fn generate_with_overflow(&self, chunk_pos: IVec3) -> (Chunk, Vec<FeatureWrite>) {
+        (self.generate(chunk_pos), Vec::new())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GeneratorKind {
+    // 3D noise blobs
+    Noise,
+    // 2D heightmap stacked from two noise samples
+    Heightmap,
+    // fBm heightmap with biomes, see biome.rs
+    Biomes,
+    // Biomes with caves and overhangs carved out, see caves.rs
+    #[default]
+    Caves,
+}
+
+// Changing this resource rebuilds `ActiveGenerator` for chunks generated afterwards
+#[derive(Resource, Clone, Debug)]
+pub struct GeneratorConfig {
+    pub kind: GeneratorKind,
+    pub seed: u64,
+    // 1 is plain Perlin noise, more adds fractal octaves
+    pub octaves: i32,
+    pub frequency: f32,
+    // World units per noise unit, bigger is smoother
+    pub noise_scale: f32,
+    pub hill_scale: f32,
+    pub continent_scale: f32,
+    pub hill_height: f32,
+    pub continent_height: f32,
+    // Added to the stacked heights
+    pub base_height: f32,
+    pub sea_level: i32,
+    // Biome generator only
+    pub fbm_octaves: i32,
+    pub warp_scale: f32,
+    pub warp_strength: f32,
+    pub biome_scale: f32,
+    // Surfaces above this height turn to snow
+    pub snow_line: i32,
+    // Cave generator only, 0 disables caves and 1 is very hollow
+    pub cave_density: f32,
+    // Depth below the surface where carving starts
+    pub cave_min_depth: f32,
+    pub cave_scale: f32,
+    // Height above the surface rock overhangs reach, 0 disables them
+    pub overhang_height: f32,
+    // Trees and boulders, see features.rs
+    pub features: bool,
+    // Chance per grass column to root a tree
+    pub tree_chance: f32,
+    // Chance per stone, sand or snow column to root a boulder
+    pub boulder_chance: f32,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            kind: GeneratorKind::default(),
+            seed: SEED,
+            octaves: 1,
+            frequency: 6.,
+            noise_scale: 100.,
+            hill_scale: 200.,
+            continent_scale: 1000.,
+            hill_height: 32.,
+            continent_height: 128.,
+            base_height: -32.,
+            sea_level: 0,
+            fbm_octaves: 5,
+            warp_scale: 300.,
+            warp_strength: 48.,
+            biome_scale: 1200.,
+            snow_line: 96,
+            cave_density: 0.5,
+            cave_min_depth: 6.,
+            cave_scale: 64.,
+            overhang_height: 16.,
+            features: true,
+            tree_chance: 0.004,
+            boulder_chance: 0.001,
+        }
+    }
+}
+
+impl GeneratorConfig {
+    pub fn noise(&self) -> FastNoise {
+        let mut noise = FastNoise::new();
+        noise.set_seed(self.seed);
+        if self.octaves > 1 {
+            noise.set_noise_type(NoiseType::PerlinFractal);
+            noise.set_fractal_type(FractalType::FBM);
+            noise.set_fractal_octaves(self.octaves);
+        } else {
+            noise.set_noise_type(NoiseType::Perlin);
+        }
+        noise.set_frequency(self.frequency);
+        noise
+    }
+    pub fn build(&self) -> Arc<dyn WorldGenerator> {
+        let terrain: Arc<dyn WorldGenerator> = match self.kind {
+            GeneratorKind::Noise => Arc::new(NoiseGenerator::new(self.clone())),
+            GeneratorKind::Heightmap => Arc::new(HeightmapGenerator::new(self.clone())),
+            GeneratorKind::Biomes => Arc::new(BiomeGenerator::new(self.clone())),
+            GeneratorKind::Caves => Arc::new(CaveGenerator::new(self.clone())),
+        };
+        if self.features {
+            Arc::new(FeatureGenerator::new(terrain, self.clone()))
+        } else {
+            terrain
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct ActiveGenerator(pub Arc<dyn WorldGenerator>);
+
+impl Default for ActiveGenerator {
+    fn default() -> Self {
+        ActiveGenerator(GeneratorConfig::default().build())
+    }
+}
+
+pub struct GeneratorPlugin;
+impl Plugin for GeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GeneratorConfig>()
+            .init_resource::<ActiveGenerator>()
+            .add_systems(PreUpdate, rebuild_generator);
+    }
+}
+
+fn rebuild_generator(config: Res<GeneratorConfig>, mut active: ResMut<ActiveGenerator>) {
+    if config.is_changed() && !config.is_added() {
+        active.0 = config.build();
+    }
+}
+
+pub struct NoiseGenerator {
+    config: GeneratorConfig,
+    noise: FastNoise,
+}
+
+impl NoiseGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        NoiseGenerator {
+            noise: config.noise(),
+            config,
+        }
+    }
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        NoiseGenerator::new(GeneratorConfig::default())
+    }
+}
+
+impl WorldGenerator for NoiseGenerator {
+    fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        let scale = self.config.noise_scale;
+        let mut data = [[[BlockId::Air; 32]; 32]; 32];
+
+        for (x, plane) in data.iter_mut().enumerate() {
+            for (y, column) in plane.iter_mut().enumerate() {
+                for (z, block) in column.iter_mut().enumerate() {
+                    let n = self.noise.get_noise3d(
+                        ((chunk_pos.x * CHUNK_SIZE + x as i32) as f32) / scale,
+                        ((chunk_pos.y * CHUNK_SIZE + y as i32) as f32) / scale,
+                        ((chunk_pos.z * CHUNK_SIZE + z as i32) as f32) / scale,
+                    );
+                    if n < 0. {
+                        *block = BlockId::Air;
+                    } else {
+                        *block = BlockId::Stone;
+                    }
+                }
+            }
+        }
+
+        Chunk {
+            data: ChunkData::from_array(&data, chunk_pos),
+            position: chunk_pos,
+            light: ChunkLight::default(),
+        }
+    }
+}
+
+pub struct HeightmapGenerator {
+    config: GeneratorConfig,
+    noise: FastNoise,
+}
+
+impl HeightmapGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        HeightmapGenerator {
+            noise: config.noise(),
+            config,
+        }
+    }
+    pub fn height(&self, x: i32, z: i32) -> f32 {
+        let config = &self.config;
+        let mut n = (self.noise.get_noise(
+            x as f32 / config.hill_scale,
+            z as f32 / config.hill_scale,
+        ) + 1.)
+            * config.hill_height
+            / 2.;
+
+        n += (self.noise.get_noise(
+            x as f32 / config.continent_scale,
+            z as f32 / config.continent_scale,
+        ) + 1.)
+            * config.continent_height
+            / 2.;
+        n + config.base_height
+    }
+}
+
+impl Default for HeightmapGenerator {
+    fn default() -> Self {
+        HeightmapGenerator::new(GeneratorConfig::default())
+    }
+}
+
+impl WorldGenerator for HeightmapGenerator {
+    fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        let mut data = [[[BlockId::Air; 32]; 32]; 32];
+
+        for (x, plane) in data.iter_mut().enumerate() {
+            for z in 0..32usize {
+                let n = self.height(
+                    chunk_pos.x * CHUNK_SIZE + x as i32,
+                    chunk_pos.z * CHUNK_SIZE + z as i32,
+                );
+
+                let sea_level = self.config.sea_level;
+                // Sand on beaches and the sea floor
+                let surface = if n < (sea_level + 2) as f32 {
+                    BlockId::Sand
+                } else {
+                    BlockId::Grass
+                };
+
+                for (y, column) in plane.iter_mut().enumerate() {
+                    let world_y = y as i32 + chunk_pos.y * CHUNK_SIZE;
+                    let depth = n - world_y as f32;
+                    column[z] = if depth <= 0. && world_y < sea_level {
+                        BlockId::Water
+                    } else if depth <= 0. {
+                        BlockId::Air
+                    } else if depth <= 1. {
+                        surface
+                    } else if depth <= 4. {
+                        BlockId::Dirt
+                    } else {
+                        BlockId::Stone
+                    };
+                }
+            }
+        }
+        Chunk {
+            data: ChunkData::from_array(&data, chunk_pos),
+            position: chunk_pos,
+            light: ChunkLight::default(),
+        }
+    }
+}
@@ -1,5 +1,21 @@
+pub mod block;
 pub mod quad;
 pub mod chunk;
+pub mod palette;
+pub mod generator;
+pub mod biome;
+pub mod caves;
+pub mod features;
+pub mod mesher;
+pub mod packed;
 pub mod world;
+pub mod light;
+pub mod lod;
+pub mod octree;
+pub mod raycast;
+pub mod region;
 pub mod tools;
 #[path ="plugins/fps.rs"] pub mod fps;
+#[path ="plugins/streaming.rs"] pub mod streaming;
+#[path ="plugins/player.rs"] pub mod player;
+#[path ="plugins/textures.rs"] pub mod textures;
@@ -0,0 +1,342 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::palette::BlockStorage;
+use crate::quad::Direction;
+use crate::world::{world_to_chunk, VoxelWorld};
+
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub const MAX_LIGHT: u8 = 15;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    // Comes straight down from the sky without fading, spreads like block light
+    Sky,
+    // Emitted by blocks, see BlockProperties::light
+    Block,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+// Light levels of a chunk, sky in the high and block light in the low nibble.
+// Chunks that are lit the same everywhere (open sky, solid rock) don't
+// allocate.
+#[derive(Clone)]
+pub struct ChunkLight {
+    levels: Option<Box<[u8]>>,
+    fill: u8,
+}
+
+// Fully sky lit, the light of an empty chunk under open sky
+impl Default for ChunkLight {
+    fn default() -> Self {
+        ChunkLight {
+            levels: None,
+            fill: MAX_LIGHT << 4,
+        }
+    }
+}
+
+fn index(local: IVec3) -> usize {
+    BlockStorage::index(local.x as usize, local.y as usize, local.z as usize)
+}
+
+impl ChunkLight {
+    pub fn dark() -> Self {
+        ChunkLight {
+            levels: None,
+            fill: 0,
+        }
+    }
+    fn packed(&self, local: IVec3) -> u8 {
+        match &self.levels {
+            Some(levels) => levels[index(local)],
+            None => self.fill,
+        }
+    }
+    pub fn get(&self, channel: LightChannel, local: IVec3) -> u8 {
+        let packed = self.packed(local);
+        match channel {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0xF,
+        }
+    }
+    pub fn set(&mut self, channel: LightChannel, local: IVec3, level: u8) {
+        let packed = self.packed(local);
+        let packed = match channel {
+            LightChannel::Sky => (packed & 0xF) | (level << 4),
+            LightChannel::Block => (packed & 0xF0) | level,
+        };
+        if self.levels.is_none() && packed == self.fill {
+            return;
+        }
+        let fill = self.fill;
+        self.levels
+            .get_or_insert_with(|| vec![fill; CHUNK_VOLUME].into_boxed_slice())[index(local)] =
+            packed;
+    }
+    // Brightest of the two channels
+    pub fn level(&self, local: IVec3) -> u8 {
+        let packed = self.packed(local);
+        (packed >> 4).max(packed & 0xF)
+    }
+    pub fn memory_usage(&self) -> usize {
+        self.levels.as_ref().map_or(0, |levels| levels.len())
+    }
+}
+
+// Vertex color multiplier for a light level
+pub fn brightness(level: u8) -> f32 {
+    0.8_f32.powi((MAX_LIGHT - level.min(MAX_LIGHT)) as i32)
+}
+
+// Voxel and light lookups for the flood fill, positions outside of what's
+// loaded return None
+trait LightAccess {
+    fn block(&self, pos: IVec3) -> Option<BlockId>;
+    fn light(&self, channel: LightChannel, pos: IVec3) -> Option<u8>;
+    fn set_light(&mut self, channel: LightChannel, pos: IVec3, level: u8);
+}
+
+// A single chunk in local coordinates
+impl LightAccess for Chunk {
+    fn block(&self, pos: IVec3) -> Option<BlockId> {
+        let (chunk, local) = world_to_chunk(pos);
+        (chunk == IVec3::ZERO).then(|| self.data.get(local.x, local.y, local.z))
+    }
+    fn light(&self, channel: LightChannel, pos: IVec3) -> Option<u8> {
+        let (chunk, local) = world_to_chunk(pos);
+        (chunk == IVec3::ZERO).then(|| self.light.get(channel, local))
+    }
+    fn set_light(&mut self, channel: LightChannel, pos: IVec3, level: u8) {
+        self.light.set(channel, pos, level);
+    }
+}
+
+impl LightAccess for VoxelWorld {
+    fn block(&self, pos: IVec3) -> Option<BlockId> {
+        self.get_voxel(pos)
+    }
+    fn light(&self, channel: LightChannel, pos: IVec3) -> Option<u8> {
+        let (chunk_pos, local) = world_to_chunk(pos);
+        let chunk = self.chunks.get(&chunk_pos)?;
+        Some(chunk.light.get(channel, local))
+    }
+    fn set_light(&mut self, channel: LightChannel, pos: IVec3, level: u8) {
+        let (chunk_pos, local) = world_to_chunk(pos);
+        let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+            return;
+        };
+        if chunk.light.get(channel, local) == level {
+            return;
+        }
+        Arc::make_mut(chunk).light.set(channel, local, level);
+        self.mark_voxel_dirty(chunk_pos, local);
+    }
+}
+
+fn emission(channel: LightChannel, block: BlockId) -> u8 {
+    match channel {
+        LightChannel::Sky => 0,
+        LightChannel::Block => block.light(),
+    }
+}
+
+// Level `dir` of a voxel lit to `level` receives
+fn spread(channel: LightChannel, level: u8, dir: Direction) -> u8 {
+    if channel == LightChannel::Sky && dir == Direction::Down && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+// Breadth first flood fill. `decrease` holds voxels that were already set to
+// 0 with the level they had, everything they lit is removed first and then
+// refilled from the brighter voxels around the hole together with `increase`.
+fn propagate(
+    access: &mut impl LightAccess,
+    channel: LightChannel,
+    mut decrease: VecDeque<(IVec3, u8)>,
+    mut increase: VecDeque<IVec3>,
+) {
+    while let Some((pos, level)) = decrease.pop_front() {
+        for dir in Direction::ALL {
+            let next = pos + dir.offset();
+            let Some(next_level) = access.light(channel, next) else {
+                continue;
+            };
+            if next_level == 0 {
+                continue;
+            }
+            if next_level < level || spread(channel, level, dir) == MAX_LIGHT {
+                access.set_light(channel, next, 0);
+                decrease.push_back((next, next_level));
+                // Emitters keep their own light
+                let own = access.block(next).map_or(0, |block| emission(channel, block));
+                if own > 0 {
+                    access.set_light(channel, next, own);
+                    increase.push_back(next);
+                }
+            } else {
+                increase.push_back(next);
+            }
+        }
+    }
+
+    while let Some(pos) = increase.pop_front() {
+        let Some(level) = access.light(channel, pos) else {
+            continue;
+        };
+        for dir in Direction::ALL {
+            let next = pos + dir.offset();
+            let target = spread(channel, level, dir);
+            if target == 0 || !access.block(next).is_some_and(|block| block.is_transparent()) {
+                continue;
+            }
+            if access.light(channel, next).is_some_and(|next_level| next_level < target) {
+                access.set_light(channel, next, target);
+                increase.push_back(next);
+            }
+        }
+    }
+}
+
+// Lights a freshly generated or loaded chunk on its own, assuming open sky
+// above it. VoxelWorld::stitch_light fixes it up against its neighbours.
+pub fn light_chunk(chunk: &mut Chunk) {
+    chunk.light = ChunkLight::dark();
+    let mut sky = VecDeque::new();
+    let mut block = VecDeque::new();
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let mut open = true;
+            for y in (0..CHUNK_SIZE).rev() {
+                let pos = IVec3::new(x, y, z);
+                let id = chunk.data.get(x, y, z);
+                open &= id.is_transparent();
+                if open {
+                    chunk.light.set(LightChannel::Sky, pos, MAX_LIGHT);
+                    sky.push_back(pos);
+                }
+                if id.light() > 0 {
+                    chunk.light.set(LightChannel::Block, pos, id.light());
+                    block.push_back(pos);
+                }
+            }
+        }
+    }
+    propagate(chunk, LightChannel::Sky, VecDeque::new(), sky);
+    propagate(chunk, LightChannel::Block, VecDeque::new(), block);
+}
+
+impl VoxelWorld {
+    // None if the chunk containing `world_pos` isn't loaded
+    pub fn get_light(&self, channel: LightChannel, world_pos: IVec3) -> Option<u8> {
+        self.light(channel, world_pos)
+    }
+
+    // Carries light across the borders of a chunk that was just added
+    pub fn stitch_light(&mut self, chunk_pos: IVec3) {
+        let Some(chunk) = self.get_chunk(chunk_pos) else {
+            return;
+        };
+        let origin = chunk_pos * CHUNK_SIZE;
+        let top = CHUNK_SIZE - 1;
+
+        for channel in LightChannel::ALL {
+            let mut decrease = VecDeque::new();
+            let mut increase = VecDeque::new();
+
+            // light_chunk assumed open sky above every chunk, take back sky
+            // light from columns that turned out to be covered
+            if channel == LightChannel::Sky {
+                for (upper, lower) in [
+                    (chunk_pos + IVec3::Y, chunk_pos),
+                    (chunk_pos, chunk_pos - IVec3::Y),
+                ] {
+                    if !self.chunks.contains_key(&upper) || !self.chunks.contains_key(&lower) {
+                        continue;
+                    }
+                    for x in 0..CHUNK_SIZE {
+                        for z in 0..CHUNK_SIZE {
+                            let above = upper * CHUNK_SIZE + IVec3::new(x, 0, z);
+                            let below = lower * CHUNK_SIZE + IVec3::new(x, top, z);
+                            let below_level = self.light(channel, below).unwrap_or(0);
+                            if below_level == MAX_LIGHT
+                                && self.light(channel, above).unwrap_or(0) < MAX_LIGHT
+                            {
+                                self.set_light(channel, below, 0);
+                                decrease.push_back((below, below_level));
+                            }
+                        }
+                    }
+                }
+            }
+
+            for dir in Direction::ALL {
+                let offset = dir.offset();
+                if !self.chunks.contains_key(&(chunk_pos + offset)) {
+                    continue;
+                }
+                let axis = dir.axis();
+                let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+                let layer = if offset[axis] > 0 { top } else { 0 };
+                for u in 0..CHUNK_SIZE {
+                    for v in 0..CHUNK_SIZE {
+                        let mut local = IVec3::ZERO;
+                        local[axis] = layer;
+                        local[u_axis] = u;
+                        local[v_axis] = v;
+                        let inside = origin + local;
+                        let outside = inside + offset;
+                        let inside_level = chunk.light.get(channel, local);
+                        let outside_level = self.light(channel, outside).unwrap_or(0);
+                        if inside_level > outside_level {
+                            increase.push_back(inside);
+                        } else if outside_level > inside_level {
+                            increase.push_back(outside);
+                        }
+                    }
+                }
+            }
+            propagate(self, channel, decrease, increase);
+        }
+    }
+
+    // Relights around a voxel after it changed to `block`
+    pub fn update_light(&mut self, world_pos: IVec3, block: BlockId) {
+        for channel in LightChannel::ALL {
+            let mut decrease = VecDeque::new();
+            let mut increase = VecDeque::new();
+
+            let old = self.light(channel, world_pos).unwrap_or(0);
+            if old > 0 {
+                self.set_light(channel, world_pos, 0);
+                decrease.push_back((world_pos, old));
+            }
+            if block.is_transparent() {
+                for dir in Direction::ALL {
+                    increase.push_back(world_pos + dir.offset());
+                }
+                // Top of the loaded world, nothing above to bring sky light in
+                if channel == LightChannel::Sky && self.get_voxel(world_pos + IVec3::Y).is_none()
+                {
+                    self.set_light(channel, world_pos, MAX_LIGHT);
+                    increase.push_back(world_pos);
+                }
+            }
+            let own = emission(channel, block);
+            if own > 0 {
+                self.set_light(channel, world_pos, own);
+                increase.push_back(world_pos);
+            }
+            propagate(self, channel, decrease, increase);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::light::MAX_LIGHT;
+use crate::mesher::{ChunkMeshes, Face, MeshBuffers, NO_AO};
+use crate::quad::Direction;
+
+use bevy::prelude::*;
+
+// Levels of detail past full resolution, level n merges 2^n voxels per axis
+pub const MAX_LOD: u32 = 3;
+
+// Chunk blocks at 1/2^level resolution, indexed (x * size + y) * size + z
+pub struct LodChunk {
+    pub level: u32,
+    pub size: i32,
+    pub blocks: Vec<BlockId>,
+}
+
+impl LodChunk {
+    pub fn get(&self, pos: IVec3) -> Option<BlockId> {
+        if pos.min_element() < 0 || pos.max_element() >= self.size {
+            return None;
+        }
+        Some(self.blocks[((pos.x * self.size + pos.y) * self.size + pos.z) as usize])
+    }
+    // Voxels per cell along each axis
+    pub fn scale(&self) -> i32 {
+        1 << self.level
+    }
+}
+
+// Each cell becomes its most common block, or Air when less than half of it
+// is filled so thin features don't grow into walls
+pub fn downsample(chunk: &Chunk, level: u32) -> LodChunk {
+    let size = CHUNK_SIZE >> level;
+    let mut blocks = Vec::with_capacity((size * size * size) as usize);
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                blocks.push(downsample_cell(chunk, level, IVec3::new(x, y, z)));
+            }
+        }
+    }
+    LodChunk {
+        level,
+        size,
+        blocks,
+    }
+}
+
+// The block of a single cell of `downsample`
+pub fn downsample_cell(chunk: &Chunk, level: u32, cell: IVec3) -> BlockId {
+    let scale = 1 << level;
+    let mut counts = [0u32; BlockId::ALL.len()];
+    for dx in 0..scale {
+        for dy in 0..scale {
+            for dz in 0..scale {
+                let pos = cell * scale + IVec3::new(dx, dy, dz);
+                counts[chunk.data.get(pos.x, pos.y, pos.z) as usize] += 1;
+            }
+        }
+    }
+    let filled = (scale * scale * scale) as u32 - counts[BlockId::Air as usize];
+    if filled * 2 < (scale * scale * scale) as u32 {
+        return BlockId::Air;
+    }
+    BlockId::ALL[1..]
+        .iter()
+        .copied()
+        .max_by_key(|block| counts[*block as usize])
+        .unwrap()
+}
+
+// One quad per exposed cell face. Faces on the chunk border are culled
+// against the neighbour downsampled to the same level. Solid faces next to
+// unloaded neighbours are kept as skirts, water stops at them.
+pub fn mesh_lod(
+    chunk: &Chunk,
+    neighbours: &ChunkNeighbours,
+    level: u32,
+    packed: bool,
+) -> ChunkMeshes {
+    let lod = downsample(chunk, level);
+    let scale = lod.scale();
+    let size = IVec3::splat(lod.size);
+    let block_at = |cell: IVec3| {
+        lod.get(cell).or_else(|| {
+            let other = neighbours.get(cell.div_euclid(size)).as_ref()?;
+            Some(downsample_cell(other, level, cell.rem_euclid(size)))
+        })
+    };
+    let mut opaque = MeshBuffers::new(packed);
+    let mut transparent = MeshBuffers::new(packed);
+
+    for x in 0..lod.size {
+        for y in 0..lod.size {
+            for z in 0..lod.size {
+                let cell = IVec3::new(x, y, z);
+                let block = lod.get(cell).unwrap();
+                if block == BlockId::Air {
+                    continue;
+                }
+                for dir in Direction::ALL {
+                    let visible = match block_at(cell + dir.offset()) {
+                        Some(other) if block.is_solid() => other.is_transparent(),
+                        Some(other) => other != block && !other.is_solid(),
+                        None => block.is_solid(),
+                    };
+                    if !visible {
+                        continue;
+                    }
+                    // Light of the full resolution voxel in front of the face
+                    let mut front = cell * scale + dir.offset();
+                    if dir.offset().max_element() > 0 {
+                        front += dir.offset() * (scale - 1);
+                    }
+                    let light = if front.min_element() < 0 || front.max_element() >= CHUNK_SIZE {
+                        MAX_LIGHT
+                    } else {
+                        chunk.light.level(front)
+                    };
+                    let buffers = if block.is_solid() {
+                        &mut opaque
+                    } else {
+                        &mut transparent
+                    };
+                    buffers.push_quad(
+                        dir,
+                        (cell * scale).as_vec3(),
+                        Vec3::splat(scale as f32),
+                        Face {
+                            block,
+                            ao: NO_AO,
+                            light,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    ChunkMeshes::new(opaque, transparent)
+}
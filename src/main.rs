@@ -15,12 +15,16 @@ use bevy::transform::commands;
 use bevy::window::PresentMode;
 use bevy_flycam::prelude::*;
 // Local imports
+use bevy_cubes::block::BlockId;
 use bevy_cubes::chunk::*;
 use bevy_cubes::fps::FpsPlugin;
-use bevy_cubes::world::VoxelWorld;
-
-#[derive(Component)]
-struct MyMesh;
+use bevy_cubes::generator::GeneratorPlugin;
+use bevy_cubes::player::{Collider, PlayerBody, PlayerPlugin, PlayerSettings};
+use bevy_cubes::raycast::RaycastHit;
+use bevy_cubes::region::ChunkStorage;
+use bevy_cubes::streaming::ChunkStreamingPlugin;
+use bevy_cubes::textures::BlockTexturePlugin;
+use bevy_cubes::world::{VoxelWorld, VoxelWorldPlugin};
 
 fn main() {
     App::new()
@@ -38,12 +42,20 @@ fn main() {
             //speed: 128.0,          // default: 12.0
             speed: 64.0, // default: 12.0
         })
-        .insert_resource(VoxelWorld::new())
+        .add_plugins(VoxelWorldPlugin)
+        .insert_resource(ChunkStorage::new("saves/world"))
+        .add_plugins(GeneratorPlugin)
+        .add_plugins(BlockTexturePlugin)
+        .add_plugins(ChunkStreamingPlugin)
+        .add_plugins(PlayerPlugin)
         .add_systems(Startup, setup)
-        .add_systems(Startup, spawn_cubes)
         .add_systems(Startup, make_hitbox_mesh)
         .add_systems(PostStartup, print_debug)
+        .add_systems(Update, print_memory_usage)
         .add_systems(PostUpdate, update_hitbox)
+        .init_resource::<TargetBlock>()
+        .add_systems(Startup, make_block_outline)
+        .add_systems(Update, (update_target_block, edit_blocks).chain())
         .run();
 }
 
@@ -147,6 +159,7 @@ fn setup(
             ..default()
         },
         FlyCam,
+        PlayerBody::default(),
     ));
 }
 fn update_hitbox(
@@ -165,38 +178,110 @@ fn update_hitbox(
     }
 }
 
-fn spawn_cubes(
+const REACH: f32 = 64.0;
+const PLACE_BLOCK: BlockId = BlockId::Stone;
+
+#[derive(Resource, Default)]
+struct TargetBlock(Option<RaycastHit>);
+
+#[derive(Component)]
+struct BlockOutline;
+
+fn make_block_outline(
     mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut voxel_world: ResMut<VoxelWorld>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    for x in 0..16 {
-        for y in 0..3 {
-            for z in 0..16 {
-                let pos = IVec3::new(x, y, z);
-                let chunk = gen_chunk_flat(pos);
-                voxel_world.add_chunk(pos, chunk.into());
+    let mut vertices: Vec<[f32; 3]> = Vec::new();
+    // Slightly bigger than a voxel so the lines don't z-fight with the faces
+    for x in [-0.005, 1.005] {
+        for y in [-0.005, 1.005] {
+            for z in [-0.005, 1.005] {
+                vertices.push([x, y, z]);
             }
         }
     }
-    for (_, chunk) in &voxel_world.chunks {
-        let mesh = chunk.gen_mesh(voxel_world.as_ref());
-        let mesh_handle = meshes.add(mesh);
-
-        commands.spawn((
-            PbrBundle {
-                mesh: mesh_handle,
-                material: materials.add(StandardMaterial {
-                    base_color: Color::srgb(0.8, 0.2, 0.2),
-                    cull_mode: Some(Face::Back),
-                    perceptual_roughness: 0.745,
-                    ..default()
-                }),
+    // Same vertex order as the hitbox
+    let indeces = vec![
+        0, 1, 1, 3, 3, 2, 2, 0, 4, 5, 5, 7, 7, 6, 6, 4, 0, 4, 2, 6, 3, 7, 1, 5,
+    ];
+    let mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_indices(Indices::U32(indeces));
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                base_color: Color::srgb(0.0, 0.0, 0.0),
+                unlit: true,
                 ..default()
-            },
-            MyMesh,
-        ));
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        BlockOutline,
+    ));
+}
+
+type OutlineQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut Visibility),
+    (With<BlockOutline>, Without<FlyCam>),
+>;
+
+fn update_target_block(
+    voxel_world: Res<VoxelWorld>,
+    mut target: ResMut<TargetBlock>,
+    camera_query: Query<&Transform, With<FlyCam>>,
+    mut outline_query: OutlineQuery,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    target.0 = voxel_world.raycast(
+        camera_transform.translation,
+        *camera_transform.forward(),
+        REACH,
+    );
+    if let Ok((mut transform, mut visibility)) = outline_query.get_single_mut() {
+        match target.0 {
+            Some(hit) => {
+                transform.translation = hit.pos.as_vec3();
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+// Left click breaks the targeted block, right click places one on the hit face
+// unless it would end up inside the player
+fn edit_blocks(
+    mouse: Res<ButtonInput<MouseButton>>,
+    target: Res<TargetBlock>,
+    settings: Res<PlayerSettings>,
+    player_query: Query<&Transform, With<PlayerBody>>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    let Some(hit) = target.0 else {
+        return;
+    };
+    if mouse.just_pressed(MouseButton::Left) {
+        voxel_world.set_voxel(hit.pos, BlockId::Air);
+    } else if mouse.just_pressed(MouseButton::Right) {
+        let pos = hit.pos + hit.face.offset();
+        let collider = Collider::new(&settings);
+        let in_player = player_query.iter().any(|transform| {
+            let feet = transform.translation - Vec3::Y * settings.eye_height;
+            collider.overlaps(feet, pos)
+        });
+        if in_player {
+            return;
+        }
+        if voxel_world.get_voxel(pos).is_some_and(|block| !block.is_solid()) {
+            voxel_world.set_voxel(pos, PLACE_BLOCK);
+        }
     }
 }
 
@@ -213,3 +298,26 @@ struct CardinalBundle {
 fn print_debug() {
     println!("NUMBER OF QUADS:{}", QUAD_COUNT.load(Ordering::SeqCst));
 }
+
+fn print_memory_usage(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    voxel_world: Res<VoxelWorld>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(5.0, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let single = voxel_world
+        .chunks
+        .values()
+        .filter(|chunk| chunk.data.single_block().is_some())
+        .count();
+    // Only shown with RUST_LOG=bevy_cubes=debug
+    debug!(
+        "CHUNKS:{} (single block:{}) BLOCK MEMORY:{:.2} MiB",
+        voxel_world.chunks.len(),
+        single,
+        voxel_world.memory_usage() as f64 / (1024. * 1024.),
+    );
+}
@@ -0,0 +1,617 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::light::{brightness, MAX_LIGHT};
+use crate::packed::{PackedVertex, ATTRIBUTE_PACKED};
+use crate::quad::{new_rect, Direction};
+
+use bevy::math::f32::Vec3;
+use bevy::prelude::*;
+use bevy::render::{
+    mesh::{Indices, MeshVertexAttribute},
+    render_asset::RenderAssetUsages,
+    render_resource::{PrimitiveTopology, VertexFormat},
+};
+use std::sync::atomic::Ordering;
+
+// Atlas tile of each vertex, see texture_tile
+pub const ATTRIBUTE_TILE: MeshVertexAttribute =
+    MeshVertexAttribute::new("Tile", 988540917, VertexFormat::Uint32);
+
+// Columns of the block texture atlas, one row per block
+pub const ATLAS_COLUMNS: u32 = 3;
+
+// Atlas tile of a block face, top/side/bottom in the block's row
+pub fn texture_tile(block: BlockId, dir: Direction) -> u32 {
+    let column = match dir {
+        Direction::Up => 0,
+        Direction::Down => 2,
+        _ => 1,
+    };
+    block.texture() as u32 * ATLAS_COLUMNS + column
+}
+
+// UVs in voxels so textures repeat across merged quads, v points down the
+// sides of blocks
+fn texture_uvs(dir: Direction, vertices: [[f32; 3]; 4], pos: Vec3, size: Vec3) -> [[f32; 2]; 4] {
+    let rel = vertices.map(|vertex| Vec3::from_array(vertex) - pos);
+    match dir.axis() {
+        0 => rel.map(|rel| [rel.z, size.y - rel.y]),
+        2 => rel.map(|rel| [rel.x, size.y - rel.y]),
+        _ => rel.map(|rel| [rel.x, rel.z]),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshingMode {
+    // One quad per exposed voxel face
+    #[default]
+    Naive,
+    // Merges coplanar faces of the same block into rectangles
+    Greedy,
+    // Same faces as Naive, culled with bitwise ops on packed columns
+    Binary,
+}
+
+pub struct ChunkMeshes {
+    pub opaque: Mesh,
+    pub transparent: Option<Mesh>,
+}
+
+impl ChunkMeshes {
+    pub fn new(opaque: MeshBuffers, transparent: MeshBuffers) -> Self {
+        // Quad num ++
+        QUAD_COUNT.fetch_add(
+            opaque.quad_count() + transparent.quad_count(),
+            Ordering::SeqCst,
+        );
+        ChunkMeshes {
+            opaque: opaque.into_mesh(),
+            transparent: (transparent.quad_count() > 0).then(|| transparent.into_mesh()),
+        }
+    }
+}
+
+// Ambient occlusion of the four corners of a face, indexed du + 2 * dv where
+// du/dv is 1 on the far side along the face's u/v axis. 3 is fully lit.
+pub type FaceAo = [u8; 4];
+pub const NO_AO: FaceAo = [3; 4];
+// Brightness for each AO level
+pub const AO_CURVE: [f32; 4] = [0.45, 0.6, 0.8, 1.0];
+
+// The two axes spanning a face, matches greedy_merge
+pub fn face_axes(dir: Direction) -> (usize, usize) {
+    let n_axis = dir.axis();
+    ((n_axis + 1) % 3, (n_axis + 2) % 3)
+}
+
+// Classic voxel AO, the two side voxels and the corner voxel touching each
+// vertex in the layer in front of the face. Missing chunks don't occlude.
+pub fn face_ao(
+    chunk: &Chunk,
+    neighbours: &ChunkNeighbours,
+    voxel_pos: IVec3,
+    dir: Direction,
+) -> FaceAo {
+    let front = voxel_pos + dir.offset();
+    corner_ao(dir, |offset| {
+        neighbours
+            .get_block(chunk, front + offset)
+            .is_some_and(|block| !block.is_transparent())
+    })
+}
+
+// AO of each corner from whether the voxel at an offset in the layer in front
+// of the face occludes
+fn corner_ao(dir: Direction, occludes: impl Fn(IVec3) -> bool) -> FaceAo {
+    let (u_axis, v_axis) = face_axes(dir);
+    let mut ao = NO_AO;
+    for (corner, ao) in ao.iter_mut().enumerate() {
+        let mut u = IVec3::ZERO;
+        let mut v = IVec3::ZERO;
+        u[u_axis] = if corner & 1 == 1 { 1 } else { -1 };
+        v[v_axis] = if corner & 2 == 2 { 1 } else { -1 };
+        let (side1, side2) = (occludes(u), occludes(v));
+        *ao = if side1 && side2 {
+            0
+        } else {
+            3 - side1 as u8 - side2 as u8 - occludes(u + v) as u8
+        };
+    }
+    ao
+}
+
+// Everything that ends up in the vertex colors of a face. Greedy meshing only
+// merges faces that are equal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Face {
+    pub block: BlockId,
+    pub ao: FaceAo,
+    // Light level of the voxel in front of the face
+    pub light: u8,
+}
+
+const NO_FACE: Face = Face {
+    block: BlockId::Air,
+    ao: NO_AO,
+    light: 0,
+};
+
+// Unloaded neighbours count as open sky
+fn face_light(chunk: &Chunk, neighbours: &ChunkNeighbours, voxel_pos: IVec3, dir: Direction) -> u8 {
+    neighbours
+        .get_light(chunk, voxel_pos + dir.offset())
+        .unwrap_or(MAX_LIGHT)
+}
+
+pub fn solid_face(
+    chunk: &Chunk,
+    neighbours: &ChunkNeighbours,
+    voxel_pos: IVec3,
+    dir: Direction,
+    block: BlockId,
+) -> Face {
+    Face {
+        block,
+        ao: face_ao(chunk, neighbours, voxel_pos, dir),
+        light: face_light(chunk, neighbours, voxel_pos, dir),
+    }
+}
+
+#[derive(Default)]
+pub struct MeshBuffers {
+    // Relative to the chunk origin, the chunk entity's Transform places them
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<Vec3>,
+    // Light and AO only, block colors come from the atlas
+    pub colors: Vec<[f32; 4]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub tiles: Vec<u32>,
+    // Everything above in one u32 per vertex, filled instead of the other
+    // attributes when `packed_vertices` is set
+    pub packed: Vec<u32>,
+    pub indices: Vec<u32>,
+    pub packed_vertices: bool,
+}
+
+impl MeshBuffers {
+    // `packed_vertices` picks the one u32 per vertex format, see packed.rs
+    pub fn new(packed_vertices: bool) -> Self {
+        MeshBuffers {
+            packed_vertices,
+            ..default()
+        }
+    }
+    // Room for `quads` more quads in the buffers this format fills
+    pub fn reserve(&mut self, quads: usize) {
+        self.indices.reserve(quads * 6);
+        if self.packed_vertices {
+            self.packed.reserve(quads * 4);
+            return;
+        }
+        self.positions.reserve(quads * 4);
+        self.normals.reserve(quads * 4);
+        self.colors.reserve(quads * 4);
+        self.uvs.reserve(quads * 4);
+        self.tiles.reserve(quads * 4);
+    }
+    pub fn push_quad(&mut self, dir: Direction, pos: Vec3, size: Vec3, face: Face) {
+        let (u_axis, v_axis) = face_axes(dir);
+        let vertices = new_rect(dir, pos, size);
+        let vertex_ao = vertices.map(|vertex| {
+            let du = (vertex[u_axis] > pos[u_axis]) as usize;
+            let dv = (vertex[v_axis] > pos[v_axis]) as usize;
+            face.ao[du + 2 * dv]
+        });
+        let i = (self.quad_count() * 4) as u32;
+        // Split along the brighter diagonal so the gradient doesn't depend on
+        // the quad orientation
+        let [a, b, c, d] = vertex_ao.map(u32::from);
+        if a + c >= b + d {
+            self.indices.extend([i, i + 1, i + 2, i + 2, i + 3, i]);
+        } else {
+            self.indices.extend([i + 1, i + 2, i + 3, i + 3, i, i + 1]);
+        }
+        if self.packed_vertices {
+            let quad = PackedVertex {
+                pos: UVec3::ZERO,
+                face: dir,
+                ao: 0,
+                light: face.light,
+                texture: face.block.texture(),
+            };
+            let positions = vertices.map(|vertex| Vec3::from_array(vertex).as_uvec3());
+            self.packed
+                .extend_from_slice(&quad.pack_quad(positions, vertex_ao));
+            return;
+        }
+        self.uvs.extend(texture_uvs(dir, vertices, pos, size));
+        self.positions.extend(vertices);
+        self.normals.extend([dir.normal(); 4]);
+        self.tiles.extend([texture_tile(face.block, dir); 4]);
+        let light = brightness(face.light);
+        self.colors.extend(vertex_ao.map(|ao| {
+            let light = light * AO_CURVE[ao as usize];
+            [light, light, light, 1.]
+        }));
+    }
+    pub fn quad_count(&self) -> usize {
+        self.indices.len() / 6
+    }
+    // Bytes of vertex and index data uploaded for this mesh
+    pub fn memory_usage(&self) -> usize {
+        self.positions.len() * size_of::<[f32; 3]>()
+            + self.normals.len() * size_of::<Vec3>()
+            + self.colors.len() * size_of::<[f32; 4]>()
+            + self.uvs.len() * size_of::<[f32; 2]>()
+            + self.tiles.len() * size_of::<u32>()
+            + self.packed.len() * size_of::<u32>()
+            + self.indices.len() * size_of::<u32>()
+    }
+    pub fn into_mesh(self) -> Mesh {
+        if self.packed_vertices {
+            return self.into_packed_mesh();
+        }
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+        .with_inserted_indices(Indices::U32(self.indices))
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+        .with_inserted_attribute(ATTRIBUTE_TILE, self.tiles)
+    }
+    // No positions, so bevy can't compute the bounds of this mesh
+    fn into_packed_mesh(self) -> Mesh {
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(ATTRIBUTE_PACKED, self.packed)
+        .with_inserted_indices(Indices::U32(self.indices))
+    }
+}
+
+pub fn mesh_naive(chunk: &Chunk, neighbours: &ChunkNeighbours, packed: bool) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new(packed);
+
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let block = chunk.data.get(x, y, z);
+                if !block.is_solid() {
+                    continue;
+                }
+                let voxel_pos = IVec3::new(x, y, z);
+                for dir in neighbours.get_voxel_neighbours(&chunk.data, voxel_pos) {
+                    let face = solid_face(chunk, neighbours, voxel_pos, dir, block);
+                    buffers.push_quad(dir, voxel_pos.as_vec3(), Vec3::ONE, face);
+                }
+            }
+        }
+    }
+    buffers
+}
+
+// Visible faces per direction, indexed [dir][x][y][z], Air marks no face
+type FaceMasks = Vec<[[[Face; 32]; 32]; 32]>;
+
+pub fn mesh_greedy(chunk: &Chunk, neighbours: &ChunkNeighbours, packed: bool) -> MeshBuffers {
+    let mut faces: FaceMasks = vec![[[[NO_FACE; 32]; 32]; 32]; 6];
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let block = chunk.data.get(x, y, z);
+                if !block.is_solid() {
+                    continue;
+                }
+                let voxel_pos = IVec3::new(x, y, z);
+                for dir in neighbours.get_voxel_neighbours(&chunk.data, voxel_pos) {
+                    faces[dir.index()][x as usize][y as usize][z as usize] =
+                        solid_face(chunk, neighbours, voxel_pos, dir, block);
+                }
+            }
+        }
+    }
+    greedy_merge(&faces, packed)
+}
+
+// Fluids and other non-solid blocks, drawn in their own alpha blended pass.
+// Faces are culled against solid blocks and the same block.
+pub fn mesh_transparent(chunk: &Chunk, neighbours: &ChunkNeighbours, packed: bool) -> MeshBuffers {
+    let mut faces: FaceMasks = vec![[[[NO_FACE; 32]; 32]; 32]; 6];
+    let mut any = false;
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let block = chunk.data.get(x, y, z);
+                if block.is_solid() || block == BlockId::Air {
+                    continue;
+                }
+                let voxel_pos = IVec3::new(x, y, z);
+                for dir in Direction::ALL {
+                    let visible = match neighbours.get_block(chunk, voxel_pos + dir.offset()) {
+                        Some(other) => other != block && !other.is_solid(),
+                        // Same world floor rule as the solid faces
+                        None => dir != Direction::Down,
+                    };
+                    if visible {
+                        faces[dir.index()][x as usize][y as usize][z as usize] = Face {
+                            block,
+                            ao: NO_AO,
+                            light: face_light(chunk, neighbours, voxel_pos, dir),
+                        };
+                        any = true;
+                    }
+                }
+            }
+        }
+    }
+    if !any {
+        return MeshBuffers::new(packed);
+    }
+    greedy_merge(&faces, packed)
+}
+
+fn greedy_merge(faces: &FaceMasks, packed: bool) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new(packed);
+    let size = CHUNK_SIZE as usize;
+
+    for dir in Direction::ALL {
+        let faces = &faces[dir.index()];
+        let n_axis = dir.axis();
+        let (u_axis, v_axis) = face_axes(dir);
+        let voxel = |slice: usize, u: usize, v: usize| {
+            let mut p = [0usize; 3];
+            p[n_axis] = slice;
+            p[u_axis] = u;
+            p[v_axis] = v;
+            p
+        };
+
+        for slice in 0..size {
+            let mut mask = [[NO_FACE; 32]; 32];
+            for (u, row) in mask.iter_mut().enumerate() {
+                for (v, face) in row.iter_mut().enumerate() {
+                    let [x, y, z] = voxel(slice, u, v);
+                    *face = faces[x][y][z];
+                }
+            }
+
+            for v in 0..size {
+                let mut u = 0;
+                while u < size {
+                    let face = mask[u][v];
+                    if face.block == BlockId::Air {
+                        u += 1;
+                        continue;
+                    }
+                    let mut width = 1;
+                    while u + width < size && mask[u + width][v] == face {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    'grow: while v + height < size {
+                        for du in 0..width {
+                            if mask[u + du][v + height] != face {
+                                break 'grow;
+                            }
+                        }
+                        height += 1;
+                    }
+                    for du in 0..width {
+                        for dv in 0..height {
+                            mask[u + du][v + dv] = NO_FACE;
+                        }
+                    }
+
+                    let [x, y, z] = voxel(slice, u, v);
+                    let mut rect = [1.; 3];
+                    rect[u_axis] = width as f32;
+                    rect[v_axis] = height as f32;
+                    buffers.push_quad(
+                        dir,
+                        Vec3::new(x as f32, y as f32, z as f32),
+                        Vec3::from_array(rect),
+                        face,
+                    );
+                    u += width;
+                }
+            }
+        }
+    }
+    buffers
+}
+
+// Bit-packed chunk, one u64 per column along z. Columns are padded with one
+// layer of the neighbouring chunks, [x + 1][y + 1] and bit z + 1, so faces
+// on the chunk border need no special case.
+pub struct BinaryChunk {
+    // Solid voxels of the chunk itself, [x][y] and bit z
+    solid: [[u32; 32]; 32],
+    // Voxels faces can be seen through. Missing chunks are open air, apart
+    // from the world floor.
+    transparent: [[u64; 34]; 34],
+    // Voxels that cast ambient occlusion, including the edge and corner
+    // chunks. Missing chunks don't occlude.
+    occluders: [[u64; 34]; 34],
+}
+
+impl BinaryChunk {
+    pub fn new(chunk: &Chunk, neighbours: &ChunkNeighbours) -> Self {
+        let [solid, transparent] = chunk
+            .data
+            .column_masks([BlockId::is_solid, BlockId::is_transparent]);
+        let mut binary = BinaryChunk {
+            solid,
+            transparent: [[0; 34]; 34],
+            occluders: [[0; 34]; 34],
+        };
+        for (x, columns) in transparent.iter().enumerate() {
+            for (y, column) in columns.iter().enumerate() {
+                binary.occluders[x + 1][y + 1] = (!column as u64) << 1;
+            }
+        }
+
+        // The layer of each neighbour touching the chunk, whole columns where
+        // the layer runs along z
+        let range = |offset: i32| match offset {
+            -1 => 31..=31,
+            0 => 0..=31,
+            _ => 0..=0,
+        };
+        let padded = |offset: i32, local: i32| (local + 1 + 32 * offset) as usize;
+        let occludes = |block: BlockId| !block.is_transparent();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let Some(neighbour) = neighbours.get(IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+                    for lx in range(x) {
+                        for ly in range(y) {
+                            let column = &mut binary.occluders[padded(x, lx)][padded(y, ly)];
+                            if z == 0 {
+                                *column |=
+                                    (neighbour.data.column_mask(lx, ly, occludes) as u64) << 1;
+                                continue;
+                            }
+                            for lz in range(z) {
+                                if occludes(neighbour.data.get(lx, ly, lz)) {
+                                    *column |= 1 << padded(z, lz);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Loaded blocks are transparent exactly where they don't occlude
+        for (transparent, occluders) in binary.transparent.iter_mut().zip(&binary.occluders) {
+            for (transparent, occluders) in transparent.iter_mut().zip(occluders) {
+                *transparent = !occluders;
+            }
+        }
+        // Faces against missing chunks are drawn, apart from the world floor
+        for dir in Direction::ALL {
+            if neighbours.get(dir.offset()).is_some() {
+                continue;
+            }
+            for i in 1..=32 {
+                match dir {
+                    Direction::North => binary.transparent[33][i] = u64::MAX,
+                    Direction::South => binary.transparent[0][i] = u64::MAX,
+                    Direction::Up => binary.transparent[i][33] = u64::MAX,
+                    Direction::Down => binary.transparent[i][0] = 0,
+                    Direction::West => {
+                        for column in &mut binary.transparent[i][1..=32] {
+                            *column |= 1 << 33;
+                        }
+                    }
+                    Direction::East => {
+                        for column in &mut binary.transparent[i][1..=32] {
+                            *column |= 1;
+                        }
+                    }
+                }
+            }
+        }
+        binary
+    }
+
+    // Same as face_ao, from the occluder bits. The 3x3 voxels in front of the
+    // face are three runs of 3 bits from the occluder columns.
+    pub fn face_ao(&self, voxel_pos: IVec3, dir: Direction) -> FaceAo {
+        let [x, y, z] = (voxel_pos + dir.offset() + IVec3::ONE)
+            .as_uvec3()
+            .to_array()
+            .map(|c| c as usize);
+        let run = |column: u64| (column >> (z - 1)) as u32 & 0b111;
+        // Bit du * u_stride + dv * v_stride for du/dv in 0..3 along the face's
+        // u/v axes
+        let (around, u_stride, v_stride) = match dir.axis() {
+            // u = y, v = z
+            0 => (
+                run(self.occluders[x][y - 1])
+                    | run(self.occluders[x][y]) << 3
+                    | run(self.occluders[x][y + 1]) << 6,
+                3,
+                1,
+            ),
+            // u = z, v = x
+            1 => (
+                run(self.occluders[x - 1][y])
+                    | run(self.occluders[x][y]) << 3
+                    | run(self.occluders[x + 1][y]) << 6,
+                1,
+                3,
+            ),
+            // u = x, v = y
+            _ => {
+                let bit = |du: usize, dv: usize| {
+                    ((self.occluders[x + du - 1][y + dv - 1] >> z) as u32 & 1) << (du + 3 * dv)
+                };
+                let around = (0..3).flat_map(|du| (0..3).map(move |dv| (du, dv)));
+                (around.map(|(du, dv)| bit(du, dv)).sum(), 1, 3)
+            }
+        };
+        let occludes = |du: usize, dv: usize| (around >> (du * u_stride + dv * v_stride)) & 1 == 1;
+        let mut ao = NO_AO;
+        for (corner, ao) in ao.iter_mut().enumerate() {
+            let du = if corner & 1 == 1 { 2 } else { 0 };
+            let dv = if corner & 2 == 2 { 2 } else { 0 };
+            let (side1, side2) = (occludes(du, 1), occludes(1, dv));
+            *ao = if side1 && side2 {
+                0
+            } else {
+                3 - side1 as u8 - side2 as u8 - occludes(du, dv) as u8
+            };
+        }
+        ao
+    }
+
+    // Visible faces in direction `dir`, [x][y] and bit z like `solid`. The
+    // voxel behind a face is in the padded column one step along the normal.
+    pub fn faces(&self, dir: Direction) -> [[u32; 32]; 32] {
+        let offset = dir.offset();
+        let (dx, dy) = ((1 + offset.x) as usize, (1 + offset.y) as usize);
+        let shift = 1 + offset.z;
+        std::array::from_fn(|x| {
+            std::array::from_fn(|y| {
+                self.solid[x][y] & (self.transparent[x + dx][y + dy] >> shift) as u32
+            })
+        })
+    }
+}
+
+pub fn mesh_binary(chunk: &Chunk, neighbours: &ChunkNeighbours, packed: bool) -> MeshBuffers {
+    let mut buffers = MeshBuffers::new(packed);
+    let binary = BinaryChunk::new(chunk, neighbours);
+    let faces = Direction::ALL.map(|dir| binary.faces(dir));
+
+    // One quad per face, so the buffers never have to grow
+    let quads = faces.iter().flatten().flatten();
+    buffers.reserve(quads.map(|faces| faces.count_ones() as usize).sum());
+
+    for (dir, faces) in Direction::ALL.into_iter().zip(&faces) {
+        for (x, columns) in faces.iter().enumerate() {
+            for (y, &column) in columns.iter().enumerate() {
+                let mut column = column;
+                while column != 0 {
+                    let z = column.trailing_zeros() as usize;
+                    column &= column - 1;
+                    let voxel_pos = IVec3::new(x as i32, y as i32, z as i32);
+                    let face = Face {
+                        block: chunk.data.get(x, y, z),
+                        ao: binary.face_ao(voxel_pos, dir),
+                        light: face_light(chunk, neighbours, voxel_pos, dir),
+                    };
+                    buffers.push_quad(dir, voxel_pos.as_vec3(), Vec3::ONE, face);
+                }
+            }
+        }
+    }
+    buffers
+}
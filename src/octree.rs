@@ -0,0 +1,263 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// Roots cover 2^ROOT_LEVEL chunks per axis, 1024 voxels at level 5
+pub const ROOT_LEVEL: u32 = 5;
+const CHUNK_VOLUME: u64 = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u64;
+
+// What's below a node, coarse enough to draw or skip far away regions
+// without touching the chunks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeSummary {
+    // Loaded chunks
+    pub chunks: u64,
+    pub solid_voxels: u64,
+    // Some if every chunk of the node is loaded and filled with this block
+    pub uniform: Option<BlockId>,
+}
+
+impl NodeSummary {
+    pub fn of_chunk(chunk: &Chunk) -> Self {
+        if let Some(block) = chunk.data.single_block() {
+            return NodeSummary::uniform(block, 0);
+        }
+        let mut solid_voxels = 0;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    solid_voxels += chunk.data.get(x, y, z).is_solid() as u64;
+                }
+            }
+        }
+        NodeSummary {
+            chunks: 1,
+            solid_voxels,
+            uniform: None,
+        }
+    }
+    // A node of 8^level chunks all made of `block`
+    pub fn uniform(block: BlockId, level: u32) -> Self {
+        let chunks = 1 << (3 * level);
+        NodeSummary {
+            chunks,
+            solid_voxels: if block.is_solid() {
+                chunks * CHUNK_VOLUME
+            } else {
+                0
+            },
+            uniform: Some(block),
+        }
+    }
+}
+
+enum Node {
+    // A single chunk, level 0 only
+    Leaf(NodeSummary),
+    // Collapsed node whose chunks are all loaded and filled with one block
+    Uniform(BlockId),
+    Branch(Box<[Option<Node>; 8]>, NodeSummary),
+}
+
+impl Node {
+    fn summary(&self, level: u32) -> NodeSummary {
+        match self {
+            Node::Leaf(summary) | Node::Branch(_, summary) => *summary,
+            Node::Uniform(block) => NodeSummary::uniform(*block, level),
+        }
+    }
+}
+
+// Child containing `pos` and its origin
+fn child(origin: IVec3, level: u32, pos: IVec3) -> (usize, IVec3) {
+    let half = 1 << (level - 1);
+    let upper = (pos - origin).cmpge(IVec3::splat(half));
+    let offset = IVec3::new(upper.x as i32, upper.y as i32, upper.z as i32);
+    (upper.bitmask() as usize, origin + offset * half)
+}
+
+fn child_origin(origin: IVec3, level: u32, index: usize) -> IVec3 {
+    let offset = IVec3::new(
+        index as i32 & 1,
+        (index as i32 >> 1) & 1,
+        (index as i32 >> 2) & 1,
+    );
+    origin + offset * (1 << (level - 1))
+}
+
+fn combine(children: &[Option<Node>; 8], level: u32) -> NodeSummary {
+    let mut summary = NodeSummary::default();
+    let mut uniform = children[0]
+        .as_ref()
+        .and_then(|node| node.summary(level).uniform);
+    for child in children {
+        let child = child
+            .as_ref()
+            .map(|node| node.summary(level))
+            .unwrap_or_default();
+        summary.chunks += child.chunks;
+        summary.solid_voxels += child.solid_voxels;
+        if child.uniform != uniform {
+            uniform = None;
+        }
+    }
+    summary.uniform = uniform;
+    summary
+}
+
+fn set(slot: &mut Option<Node>, origin: IVec3, level: u32, pos: IVec3, leaf: Option<NodeSummary>) {
+    if level == 0 {
+        *slot = leaf.map(Node::Leaf);
+        return;
+    }
+    // Split collapsed nodes back up before changing one of their chunks
+    if let Some(Node::Uniform(block)) = slot {
+        let block = *block;
+        let children = std::array::from_fn(|_| {
+            Some(if level == 1 {
+                Node::Leaf(NodeSummary::uniform(block, 0))
+            } else {
+                Node::Uniform(block)
+            })
+        });
+        *slot = Some(Node::Branch(
+            Box::new(children),
+            NodeSummary::uniform(block, level),
+        ));
+    }
+    if slot.is_none() {
+        if leaf.is_none() {
+            return;
+        }
+        *slot = Some(Node::Branch(Box::default(), NodeSummary::default()));
+    }
+    let Some(Node::Branch(children, summary)) = slot else {
+        unreachable!()
+    };
+
+    let (index, child_origin) = child(origin, level, pos);
+    set(&mut children[index], child_origin, level - 1, pos, leaf);
+
+    let combined = combine(children, level - 1);
+    if combined.chunks == 0 {
+        *slot = None;
+    } else if let Some(block) = combined.uniform {
+        *slot = Some(Node::Uniform(block));
+    } else {
+        *summary = combined;
+    }
+}
+
+fn query(node: &Node, origin: IVec3, level: u32, min: IVec3, max: IVec3, out: &mut Vec<IVec3>) {
+    let end = origin + IVec3::splat((1 << level) - 1);
+    if end.cmplt(min).any() || origin.cmpgt(max).any() {
+        return;
+    }
+    match node {
+        Node::Leaf(_) => out.push(origin),
+        Node::Uniform(_) => {
+            let (from, to) = (origin.max(min), end.min(max));
+            for x in from.x..=to.x {
+                for y in from.y..=to.y {
+                    for z in from.z..=to.z {
+                        out.push(IVec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        Node::Branch(children, _) => {
+            for (index, child) in children.iter().enumerate() {
+                if let Some(child) = child {
+                    let origin = child_origin(origin, level, index);
+                    query(child, origin, level - 1, min, max, out);
+                }
+            }
+        }
+    }
+}
+
+fn count_nodes(node: &Node) -> usize {
+    match node {
+        Node::Branch(children, _) => 1 + children.iter().flatten().map(count_nodes).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+// Sparse octree over loaded chunk positions. Holds summaries only, the chunks
+// themselves stay in VoxelWorld::chunks.
+#[derive(Default)]
+pub struct ChunkOctree {
+    roots: HashMap<IVec3, Node>,
+}
+
+impl ChunkOctree {
+    fn root(pos: IVec3) -> (IVec3, IVec3) {
+        let key = pos.div_euclid(IVec3::splat(1 << ROOT_LEVEL));
+        (key, key * (1 << ROOT_LEVEL))
+    }
+    fn set(&mut self, pos: IVec3, leaf: Option<NodeSummary>) {
+        let (key, origin) = Self::root(pos);
+        let mut slot = self.roots.remove(&key);
+        set(&mut slot, origin, ROOT_LEVEL, pos, leaf);
+        if let Some(node) = slot {
+            self.roots.insert(key, node);
+        }
+    }
+    pub fn insert(&mut self, pos: IVec3, summary: NodeSummary) {
+        // A leaf is one loaded chunk whatever the summary says, empty nodes
+        // are dropped by `set`
+        self.set(pos, Some(NodeSummary { chunks: 1, ..summary }));
+    }
+    pub fn remove(&mut self, pos: IVec3) {
+        self.set(pos, None);
+    }
+
+    // Summary of the node of size 2^level chunks containing chunk `pos`,
+    // None if nothing below it is loaded
+    pub fn summary(&self, pos: IVec3, level: u32) -> Option<NodeSummary> {
+        let (key, mut origin) = Self::root(pos);
+        let mut node = self.roots.get(&key)?;
+        let mut node_level = ROOT_LEVEL;
+        while node_level > level {
+            match node {
+                Node::Uniform(block) => return Some(NodeSummary::uniform(*block, level)),
+                Node::Branch(children, _) => {
+                    let (index, child_origin) = child(origin, node_level, pos);
+                    node = children[index].as_ref()?;
+                    origin = child_origin;
+                    node_level -= 1;
+                }
+                Node::Leaf(_) => unreachable!(),
+            }
+        }
+        Some(node.summary(node_level))
+    }
+
+    // Loaded chunk positions in the box from `min` to `max`, inclusive
+    pub fn chunks_in(&self, min: IVec3, max: IVec3) -> Vec<IVec3> {
+        let mut out = Vec::new();
+        let (min_key, _) = Self::root(min);
+        let (max_key, _) = Self::root(max);
+        for (key, node) in &self.roots {
+            if key.cmplt(min_key).any() || key.cmpgt(max_key).any() {
+                continue;
+            }
+            query(
+                node,
+                *key * (1 << ROOT_LEVEL),
+                ROOT_LEVEL,
+                min,
+                max,
+                &mut out,
+            );
+        }
+        out
+    }
+
+    // Nodes allocated, collapsed regions count as one
+    pub fn node_count(&self) -> usize {
+        self.roots.values().map(count_nodes).sum()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::block::BLOCK_COUNT;
+use crate::quad::Direction;
+
+use bevy::prelude::*;
+use bevy::render::{mesh::MeshVertexAttribute, render_resource::VertexFormat};
+
+// Whole chunk vertex in a single u32, decoded by the PACKED_VERTICES variant
+// of shaders/voxel.wgsl. Bits from the lowest:
+//   x, y, z   6 each, chunk local so 0..=32
+//   face      3, index into Direction::ALL
+//   ao        2, same levels as FaceAo
+//   light     4
+//   texture   5, atlas row, the column follows from the face
+pub const ATTRIBUTE_PACKED: MeshVertexAttribute =
+    MeshVertexAttribute::new("Packed", 988540918, VertexFormat::Uint32);
+
+const POS_BITS: u32 = 6;
+const FACE_SHIFT: u32 = 3 * POS_BITS;
+const AO_SHIFT: u32 = FACE_SHIFT + 3;
+const LIGHT_SHIFT: u32 = AO_SHIFT + 2;
+const TEXTURE_SHIFT: u32 = LIGHT_SHIFT + 4;
+// Atlas rows that fit in the remaining bits
+pub const MAX_PACKED_TEXTURES: u32 = 1 << (32 - TEXTURE_SHIFT);
+// One atlas row per block
+const _: () = assert!(BLOCK_COUNT as u32 <= MAX_PACKED_TEXTURES);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedVertex {
+    pub pos: UVec3,
+    pub face: Direction,
+    pub ao: u8,
+    pub light: u8,
+    pub texture: u16,
+}
+
+fn bits(value: u32, shift: u32, count: u32) -> u32 {
+    (value >> shift) & ((1 << count) - 1)
+}
+
+fn pos_bits(pos: UVec3) -> u32 {
+    pos.x | (pos.y << POS_BITS) | (pos.z << (2 * POS_BITS))
+}
+
+impl PackedVertex {
+    pub fn pack(self) -> u32 {
+        debug_assert!(self.pos.max_element() < 1 << POS_BITS);
+        debug_assert!(self.ao < 4 && self.light < 16);
+        debug_assert!((self.texture as u32) < MAX_PACKED_TEXTURES);
+        let face = self.face.index() as u32;
+        pos_bits(self.pos)
+            | (face << FACE_SHIFT)
+            | (self.ao as u32) << AO_SHIFT
+            | (self.light as u32) << LIGHT_SHIFT
+            | (self.texture as u32) << TEXTURE_SHIFT
+    }
+    // The four vertices of a quad, which only differ in position and AO.
+    // `self` holds the rest, its own pos and ao are ignored.
+    pub fn pack_quad(self, positions: [UVec3; 4], ao: [u8; 4]) -> [u32; 4] {
+        let shared = PackedVertex {
+            pos: UVec3::ZERO,
+            ao: 0,
+            ..self
+        }
+        .pack();
+        std::array::from_fn(|i| {
+            debug_assert!(positions[i].max_element() < 1 << POS_BITS && ao[i] < 4);
+            shared | pos_bits(positions[i]) | (ao[i] as u32) << AO_SHIFT
+        })
+    }
+    // None when the face bits are 6 or 7, pack never writes those
+    pub fn unpack(packed: u32) -> Option<Self> {
+        let face = *Direction::ALL.get(bits(packed, FACE_SHIFT, 3) as usize)?;
+        Some(PackedVertex {
+            pos: UVec3::new(
+                bits(packed, 0, POS_BITS),
+                bits(packed, POS_BITS, POS_BITS),
+                bits(packed, 2 * POS_BITS, POS_BITS),
+            ),
+            face,
+            ao: bits(packed, AO_SHIFT, 2) as u8,
+            light: bits(packed, LIGHT_SHIFT, 4) as u8,
+            texture: bits(packed, TEXTURE_SHIFT, 32 - TEXTURE_SHIFT) as u16,
+        })
+    }
+}
@@ -0,0 +1,251 @@
+use crate::block::BlockId;
+
+const VOLUME: usize = 32 * 32 * 32;
+
+// Compressed storage for the 32^3 blocks of a chunk
+#[derive(Clone, Debug)]
+pub enum BlockStorage {
+    // Every block is the same, no per-voxel data
+    Single(BlockId),
+    // Per-voxel index into `palette`, `bits` wide (1, 2, 4 or 8) and packed
+    // into u64 words so no index straddles two words
+    Paletted {
+        palette: Vec<BlockId>,
+        bits: u32,
+        words: Vec<u64>,
+    },
+}
+
+impl Default for BlockStorage {
+    fn default() -> Self {
+        BlockStorage::Single(BlockId::Air)
+    }
+}
+
+fn bits_for(palette_len: usize) -> u32 {
+    match palette_len {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+fn word_count(bits: u32) -> usize {
+    VOLUME / (64 / bits as usize)
+}
+
+fn read(words: &[u64], bits: u32, index: usize) -> usize {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    ((words[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn write(words: &mut [u64], bits: u32, index: usize, value: usize) {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    let mask = ((1u64 << bits) - 1) << shift;
+    let word = &mut words[index / per_word];
+    *word = (*word & !mask) | ((value as u64) << shift);
+}
+
+// Column masks of N block tests at once, see BlockStorage::column_masks.
+// Each byte of a word is looked up as a whole in `bytes`, which holds the
+// test results of the 8 / BITS indices packed into that byte.
+fn decode_masks<const BITS: usize, const N: usize>(
+    words: &[u64],
+    bytes: &[[u8; 256]; N],
+) -> [[[u32; 32]; 32]; N] {
+    let per_word = 64 / BITS;
+    let per_byte = 8 / BITS;
+    let mut masks = [[[0u32; 32]; 32]; N];
+    for (w, &word) in words.iter().enumerate() {
+        let first = w * per_word;
+        for (masks, bytes) in masks.iter_mut().zip(bytes) {
+            let mut bits = 0u64;
+            for (b, byte) in word.to_le_bytes().into_iter().enumerate() {
+                bits |= (bytes[byte as usize] as u64) << (b * per_byte);
+            }
+            // A word covers part of a column, or two whole columns for 1 bit
+            // indices
+            let column = first / 32;
+            if per_word == 64 {
+                masks[column / 32][column % 32] = bits as u32;
+                masks[(column + 1) / 32][(column + 1) % 32] = (bits >> 32) as u32;
+            } else {
+                masks[column / 32][column % 32] |= (bits as u32) << (first % 32);
+            }
+        }
+    }
+    masks
+}
+
+impl BlockStorage {
+    // `blocks` in the same x, y, z order as `index`
+    pub fn from_blocks(blocks: impl Iterator<Item = BlockId> + Clone) -> Self {
+        let mut palette: Vec<BlockId> = Vec::new();
+        for block in blocks.clone() {
+            if !palette.contains(&block) {
+                palette.push(block);
+            }
+        }
+        if palette.len() <= 1 {
+            return BlockStorage::Single(palette.first().copied().unwrap_or_default());
+        }
+        let bits = bits_for(palette.len());
+        let mut words = vec![0; word_count(bits)];
+        for (index, block) in blocks.enumerate() {
+            let value = palette.iter().position(|b| *b == block).unwrap();
+            write(&mut words, bits, index, value);
+        }
+        BlockStorage::Paletted {
+            palette,
+            bits,
+            words,
+        }
+    }
+
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        (x * 32 + y) * 32 + z
+    }
+
+    pub fn get(&self, index: usize) -> BlockId {
+        match self {
+            BlockStorage::Single(block) => *block,
+            BlockStorage::Paletted {
+                palette,
+                bits,
+                words,
+            } => palette[read(words, *bits, index)],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, block: BlockId) {
+        if let BlockStorage::Single(current) = *self {
+            if current == block {
+                return;
+            }
+            // Upgrade, index 0 is the block the whole chunk used to be
+            *self = BlockStorage::Paletted {
+                palette: vec![current, block],
+                bits: 1,
+                words: vec![0; word_count(1)],
+            };
+        }
+        let BlockStorage::Paletted {
+            palette,
+            bits,
+            words,
+        } = self
+        else {
+            unreachable!()
+        };
+        let value = match palette.iter().position(|b| *b == block) {
+            Some(value) => value,
+            None => {
+                palette.push(block);
+                let needed = bits_for(palette.len());
+                if needed > *bits {
+                    // Repack with wider indices
+                    let mut wider = vec![0; word_count(needed)];
+                    for i in 0..VOLUME {
+                        write(&mut wider, needed, i, read(words, *bits, i));
+                    }
+                    *words = wider;
+                    *bits = needed;
+                }
+                palette.len() - 1
+            }
+        };
+        write(words, *bits, index, value);
+    }
+
+    // Drops unused palette entries and falls back to Single when possible
+    pub fn compact(&mut self) {
+        if let BlockStorage::Paletted { .. } = self {
+            let storage = &*self;
+            *self = BlockStorage::from_blocks((0..VOLUME).map(|i| storage.get(i)));
+        }
+    }
+
+    // Bit z of [x][y] of the n-th mask is set where the n-th test holds for
+    // the block at x, y, z. Decodes the words in order instead of one index at
+    // a time.
+    pub fn column_masks<const N: usize>(
+        &self,
+        tests: [fn(BlockId) -> bool; N],
+    ) -> [[[u32; 32]; 32]; N] {
+        let (palette, bits, words) = match self {
+            BlockStorage::Single(block) => {
+                return tests.map(|test| [[if test(*block) { u32::MAX } else { 0 }; 32]; 32]);
+            }
+            BlockStorage::Paletted {
+                palette,
+                bits,
+                words,
+            } => (palette, *bits, words),
+        };
+        let mut matches = [[false; N]; 256];
+        for (value, block) in palette.iter().enumerate() {
+            matches[value] = tests.map(|test| test(*block));
+        }
+        // Results of every index packed into a byte, for each possible byte
+        let per_byte = 8 / bits as usize;
+        let value_mask = (1 << bits) - 1;
+        let mut bytes = [[0u8; 256]; N];
+        for (n, bytes) in bytes.iter_mut().enumerate() {
+            for (byte, result) in bytes.iter_mut().enumerate() {
+                for k in 0..per_byte {
+                    let value = (byte >> (k * bits as usize)) & value_mask;
+                    *result |= (matches[value][n] as u8) << k;
+                }
+            }
+        }
+        match bits {
+            1 => decode_masks::<1, N>(words, &bytes),
+            2 => decode_masks::<2, N>(words, &bytes),
+            4 => decode_masks::<4, N>(words, &bytes),
+            _ => decode_masks::<8, N>(words, &bytes),
+        }
+    }
+
+    // Bit z set where `test` holds for the 32 blocks from `first`, the blocks
+    // of one column along z
+    pub fn column_mask(&self, first: usize, test: impl Fn(BlockId) -> bool) -> u32 {
+        match self {
+            BlockStorage::Single(block) => {
+                if test(*block) {
+                    u32::MAX
+                } else {
+                    0
+                }
+            }
+            BlockStorage::Paletted {
+                palette,
+                bits,
+                words,
+            } => (0..32).fold(0, |mask, z| {
+                mask | (test(palette[read(words, *bits, first + z)]) as u32) << z
+            }),
+        }
+    }
+
+    pub fn single_block(&self) -> Option<BlockId> {
+        match self {
+            BlockStorage::Single(block) => Some(*block),
+            BlockStorage::Paletted { .. } => None,
+        }
+    }
+
+    // Heap and inline bytes used by this storage
+    pub fn memory_usage(&self) -> usize {
+        let heap = match self {
+            BlockStorage::Single(_) => 0,
+            BlockStorage::Paletted { palette, words, .. } => {
+                palette.capacity() * std::mem::size_of::<BlockId>()
+                    + words.capacity() * std::mem::size_of::<u64>()
+            }
+        };
+        std::mem::size_of::<Self>() + heap
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use bevy_flycam::prelude::*;
+
+use crate::chunk::CHUNK_SIZE;
+use crate::world::VoxelWorld;
+
+// Same dimensions as the hitbox drawn in main.rs
+#[derive(Resource)]
+pub struct PlayerSettings {
+    pub width: f32,
+    pub height: f32,
+    // Camera height above the feet
+    pub eye_height: f32,
+    pub walk_speed: f32,
+    pub jump_speed: f32,
+    pub gravity: f32,
+    // Terminal velocity, falls never get faster than this
+    pub max_fall_speed: f32,
+    // Highest ledge walked onto without jumping
+    pub step_height: f32,
+    pub toggle_key: KeyCode,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            width: 8.0,
+            height: 24.0,
+            eye_height: 20.0,
+            walk_speed: 48.0,
+            jump_speed: 64.0,
+            gravity: 160.0,
+            max_fall_speed: 256.0,
+            step_height: 6.0,
+            toggle_key: KeyCode::KeyF,
+        }
+    }
+}
+
+#[derive(Resource, Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum MovementMode {
+    // bevy_flycam flying through everything
+    #[default]
+    Noclip,
+    Walking,
+}
+
+#[derive(Component, Default)]
+pub struct PlayerBody {
+    pub velocity: Vec3,
+    pub on_ground: bool,
+}
+
+// Longest frame simulated in one go, a hitch doesn't launch the player
+const MAX_DT: f32 = 0.1;
+
+// Fly speed to restore when going back to noclip
+#[derive(Resource, Default)]
+struct NoclipSpeed(f32);
+
+pub struct PlayerPlugin;
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerSettings>()
+            .init_resource::<MovementMode>()
+            .init_resource::<NoclipSpeed>()
+            .add_systems(Update, (toggle_movement_mode, walk).chain());
+    }
+}
+
+fn toggle_movement_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    settings: Res<PlayerSettings>,
+    mut mode: ResMut<MovementMode>,
+    mut noclip_speed: ResMut<NoclipSpeed>,
+    mut movement: ResMut<MovementSettings>,
+    mut bodies: Query<&mut PlayerBody>,
+) {
+    if !keys.just_pressed(settings.toggle_key) {
+        return;
+    }
+    *mode = match *mode {
+        MovementMode::Noclip => {
+            // The flycam keeps handling mouse look, only its movement is turned off
+            noclip_speed.0 = movement.speed;
+            movement.speed = 0.0;
+            MovementMode::Walking
+        }
+        MovementMode::Walking => {
+            movement.speed = noclip_speed.0;
+            MovementMode::Noclip
+        }
+    };
+    for mut body in &mut bodies {
+        *body = PlayerBody::default();
+    }
+}
+
+fn walk(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    settings: Res<PlayerSettings>,
+    mode: Res<MovementMode>,
+    voxel_world: Res<VoxelWorld>,
+    mut query: Query<(&mut Transform, &mut PlayerBody), With<FlyCam>>,
+) {
+    if *mode != MovementMode::Walking {
+        return;
+    }
+    let Ok((mut transform, mut body)) = query.get_single_mut() else {
+        return;
+    };
+    let mut feet = transform.translation - Vec3::Y * settings.eye_height;
+    // Wait for the ground to be generated before falling into it
+    let chunk_pos = (feet / CHUNK_SIZE as f32).floor().as_ivec3();
+    if voxel_world.get_chunk(chunk_pos).is_none() {
+        return;
+    }
+
+    let forward = transform.forward().with_y(0.).normalize_or_zero();
+    let right = transform.right().with_y(0.).normalize_or_zero();
+    let mut wish = Vec3::ZERO;
+    for key in keys.get_pressed() {
+        if *key == key_bindings.move_forward {
+            wish += forward;
+        } else if *key == key_bindings.move_backward {
+            wish -= forward;
+        } else if *key == key_bindings.move_right {
+            wish += right;
+        } else if *key == key_bindings.move_left {
+            wish -= right;
+        }
+    }
+    let wish = wish.normalize_or_zero() * settings.walk_speed;
+    body.velocity.x = wish.x;
+    body.velocity.z = wish.z;
+    if body.on_ground && keys.pressed(key_bindings.move_ascend) {
+        body.velocity.y = settings.jump_speed;
+    }
+    let dt = time.delta_seconds().min(MAX_DT);
+    body.velocity.y = (body.velocity.y - settings.gravity * dt).max(-settings.max_fall_speed);
+
+    let collider = Collider::new(&settings);
+    let delta = body.velocity * dt;
+    let was_on_ground = body.on_ground;
+    body.on_ground = false;
+
+    // Vertical first so the step-up below starts from the ground
+    if collider.move_axis(&voxel_world, &mut feet, 1, delta.y) {
+        if delta.y < 0. {
+            body.on_ground = true;
+        }
+        body.velocity.y = 0.;
+    }
+    for axis in [0, 2] {
+        let step_height = if was_on_ground || body.on_ground {
+            settings.step_height
+        } else {
+            0.
+        };
+        collider.step_axis(&voxel_world, &mut feet, axis, delta[axis], step_height);
+    }
+
+    transform.translation = feet + Vec3::Y * settings.eye_height;
+}
+
+// Box around the player, standing on `feet` in the middle of its bottom face
+pub struct Collider {
+    pub half_width: f32,
+    pub height: f32,
+}
+
+impl Collider {
+    pub fn new(settings: &PlayerSettings) -> Self {
+        Collider {
+            half_width: settings.width / 2.,
+            height: settings.height,
+        }
+    }
+
+    // First and last voxel the box overlaps, inclusive. Shrunk a little so
+    // touching a face doesn't count as overlapping.
+    fn voxels(&self, feet: Vec3) -> (IVec3, IVec3) {
+        let min = (feet - Vec3::new(self.half_width, 0., self.half_width) + 1e-3)
+            .floor()
+            .as_ivec3();
+        let max = (feet + Vec3::new(self.half_width, self.height, self.half_width) - 1e-3)
+            .floor()
+            .as_ivec3();
+        (min, max)
+    }
+
+    pub fn overlaps(&self, feet: Vec3, voxel: IVec3) -> bool {
+        let (min, max) = self.voxels(feet);
+        voxel.cmpge(min).all() && voxel.cmple(max).all()
+    }
+
+    fn intersects(&self, voxel_world: &VoxelWorld, feet: Vec3) -> bool {
+        let (min, max) = self.voxels(feet);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let block = voxel_world.get_voxel(IVec3::new(x, y, z));
+                    if block.is_some_and(|block| block.is_solid()) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    // Sweeps `feet` along one axis, stopping flush against the first solid voxel.
+    // Returns true if it collided.
+    pub fn move_axis(
+        &self,
+        voxel_world: &VoxelWorld,
+        feet: &mut Vec3,
+        axis: usize,
+        delta: f32,
+    ) -> bool {
+        // Steps of at most half a voxel so fast moves can't tunnel
+        let steps = (delta.abs() * 2.).ceil().max(1.) as usize;
+        let step = delta / steps as f32;
+        for _ in 0..steps {
+            let mut next = *feet;
+            next[axis] += step;
+            if !self.intersects(voxel_world, next) {
+                *feet = next;
+                continue;
+            }
+            // Snap to the voxel boundary the box was about to cross
+            let extent = match axis {
+                1 if step > 0. => self.height,
+                1 => 0.,
+                _ if step > 0. => self.half_width,
+                _ => -self.half_width,
+            };
+            let edge = feet[axis] + extent;
+            let boundary = if step > 0. {
+                (edge + step).floor()
+            } else {
+                (edge + step).ceil()
+            };
+            next[axis] = boundary - extent;
+            if !self.intersects(voxel_world, next) {
+                *feet = next;
+            }
+            return true;
+        }
+        false
+    }
+
+    // Same as move_axis but walks onto ledges up to `step_height` high
+    // instead of stopping. Returns true if it still collided.
+    pub fn step_axis(
+        &self,
+        voxel_world: &VoxelWorld,
+        feet: &mut Vec3,
+        axis: usize,
+        delta: f32,
+        step_height: f32,
+    ) -> bool {
+        let start = *feet;
+        if !self.move_axis(voxel_world, feet, axis, delta) {
+            return false;
+        }
+        if step_height <= 0. {
+            return true;
+        }
+        // Retry the move raised by step_height, then settle back down
+        let mut stepped = start;
+        if self.move_axis(voxel_world, &mut stepped, 1, step_height)
+            || self.move_axis(voxel_world, &mut stepped, axis, delta)
+        {
+            return true;
+        }
+        self.move_axis(voxel_world, &mut stepped, 1, -step_height);
+        *feet = stepped;
+        false
+    }
+}
@@ -0,0 +1,545 @@
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, IoTaskPool, Task};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::chunk::*;
+use crate::features::{apply_writes, FeatureWrite};
+use crate::generator::ActiveGenerator;
+use crate::light::light_chunk;
+use crate::lod::{mesh_lod, MAX_LOD};
+use crate::mesher::{ChunkMeshes, MeshingMode};
+use crate::octree::NodeSummary;
+use crate::quad::Direction;
+use crate::region::ChunkStorage;
+use crate::textures::VoxelMaterial;
+use crate::world::{chunk_origin, ChunkEntity, VoxelWorld};
+
+#[derive(Resource)]
+pub struct StreamingSettings {
+    // In chunks, measured on the xz plane around the camera chunk
+    pub render_distance: i32,
+    pub vertical_distance: i32,
+    // Generation tasks started per frame
+    pub chunks_per_frame: usize,
+    // Finished generation/meshing tasks applied per frame
+    pub results_per_frame: usize,
+    pub meshing_mode: MeshingMode,
+    // One u32 per vertex instead of the full attributes, see packed.rs
+    pub packed_vertices: bool,
+    // Chunk distance from which each coarser level of detail is used,
+    // lod_distances[0] switches to 2x2x2 voxel cells. Levels past
+    // render_distance are never loaded.
+    pub lod_distances: [i32; MAX_LOD as usize],
+    // Seconds between writes of edited chunks to the region files
+    pub save_interval: f32,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        StreamingSettings {
+            render_distance: 8,
+            vertical_distance: 3,
+            chunks_per_frame: 32,
+            results_per_frame: 16,
+            meshing_mode: MeshingMode::Greedy,
+            packed_vertices: true,
+            lod_distances: [3, 5, 7],
+            save_interval: 2.0,
+        }
+    }
+}
+
+// Both inserted by BlockTexturePlugin once the atlas is built, chunks aren't
+// shown before that
+#[derive(Resource)]
+pub struct ChunkMaterial(pub Handle<VoxelMaterial>);
+
+// Alpha blended material for the transparent (water) pass
+#[derive(Resource)]
+pub struct TransparentChunkMaterial(pub Handle<VoxelMaterial>);
+
+#[derive(Component)]
+pub struct ChunkMesh;
+
+// Child of a ChunkMesh entity
+#[derive(Component)]
+pub struct TransparentChunkMesh;
+
+// Feature writes are None for chunks loaded from storage, they already
+// passed theirs on when they were first generated. The summary for the
+// octree is computed on the task pool too.
+#[derive(Component)]
+pub struct GenerateTask(Task<(Chunk, NodeSummary, Option<Vec<FeatureWrite>>)>);
+
+#[derive(Component)]
+pub struct MeshTask(Task<ChunkMeshes>);
+
+// Region writes run on the IO pool, one batch at a time so two writes never
+// race on the same file
+#[derive(Resource, Default)]
+pub struct ChunkSaves {
+    task: Option<Task<()>>,
+    // Chunks written by the running task, and unloaded chunks and feature
+    // writes waiting for the next batch. None of them is loaded back before
+    // it's on disk.
+    saving: HashSet<IVec3>,
+    queued: HashMap<IVec3, Arc<Chunk>>,
+    features: HashMap<IVec3, Vec<FeatureWrite>>,
+    since_flush: f32,
+}
+
+type SaveBatch = (Vec<Arc<Chunk>>, Vec<(IVec3, Vec<FeatureWrite>)>);
+
+impl ChunkSaves {
+    pub fn is_pending(&self, pos: IVec3) -> bool {
+        self.saving.contains(&pos)
+            || self.queued.contains_key(&pos)
+            || self.features.contains_key(&pos)
+    }
+
+    fn queue_features(&mut self, features: Vec<(IVec3, Vec<FeatureWrite>)>) {
+        for (pos, writes) in features {
+            self.features.entry(pos).or_default().extend(writes);
+        }
+    }
+
+    // Everything waiting, with the chunks still loaded that were edited
+    fn take_batch(&mut self, voxel_world: &mut VoxelWorld) -> SaveBatch {
+        let unsaved: Vec<IVec3> = voxel_world.unsaved.drain().collect();
+        for pos in unsaved {
+            if let Some(chunk) = voxel_world.get_chunk(pos) {
+                self.queued.insert(pos, chunk);
+            }
+        }
+        self.saving.extend(self.queued.keys().chain(self.features.keys()));
+        let chunks = self.queued.drain().map(|(_, chunk)| chunk).collect();
+        (chunks, self.features.drain().collect())
+    }
+}
+
+fn save_batch(storage: &ChunkStorage, (chunks, features): &SaveBatch) {
+    if let Err(e) = storage.save_chunks(chunks.iter().map(|chunk| &**chunk)) {
+        error!("failed to save {} chunks: {}", chunks.len(), e);
+    }
+    let features = features.iter().map(|(pos, writes)| (*pos, writes.as_slice()));
+    if let Err(e) = storage.save_feature_writes(features) {
+        error!("failed to save feature writes: {}", e);
+    }
+}
+
+pub struct ChunkStreamingPlugin;
+impl Plugin for ChunkStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        // Falls back to the default generator without GeneratorPlugin
+        app.init_resource::<StreamingSettings>()
+            .init_resource::<ActiveGenerator>()
+            .init_resource::<ChunkSaves>()
+            .add_systems(
+                Update,
+                (
+                    save_edited_chunks,
+                    unload_chunks,
+                    load_chunks,
+                    poll_generate_tasks,
+                    update_lods,
+                    remesh_dirty_chunks,
+                    poll_mesh_tasks,
+                )
+                    .chain(),
+            )
+            .add_systems(Last, save_on_exit);
+    }
+}
+
+fn camera_chunk(camera_query: &Query<&Transform, With<Camera3d>>) -> Option<IVec3> {
+    let transform = camera_query.get_single().ok()?;
+    Some((transform.translation / CHUNK_SIZE as f32).floor().as_ivec3())
+}
+
+pub fn chunk_lod(settings: &StreamingSettings, center: IVec3, pos: IVec3) -> u32 {
+    let distance = (pos - center).xz().as_vec2().length();
+    settings
+        .lod_distances
+        .iter()
+        .filter(|lod_distance| distance >= **lod_distance as f32)
+        .count() as u32
+}
+
+// Smallest box around everything in_range, inclusive
+fn range_box(settings: &StreamingSettings, center: IVec3, margin: i32) -> (IVec3, IVec3) {
+    let distance = settings.render_distance + margin;
+    let extent = IVec3::new(distance, settings.vertical_distance + margin, distance);
+    (center - extent, center + extent)
+}
+
+fn in_range(settings: &StreamingSettings, center: IVec3, pos: IVec3, margin: i32) -> bool {
+    let offset = pos - center;
+    let distance = settings.render_distance + margin;
+    offset.xz().length_squared() <= distance * distance
+        && offset.y.abs() <= settings.vertical_distance + margin
+}
+
+// Starts meshing `pos` on the task pool, the result replaces the entity's mesh
+pub fn spawn_mesh_task(
+    commands: &mut Commands,
+    voxel_world: &VoxelWorld,
+    entity: Entity,
+    pos: IVec3,
+    mode: MeshingMode,
+    packed: bool,
+) {
+    let Some(chunk) = voxel_world.get_chunk(pos) else {
+        return;
+    };
+    let lod = voxel_world.lods.get(&pos).copied().unwrap_or(0);
+    let mut neighbours = ChunkNeighbours::new(voxel_world, pos);
+    if lod > 0 {
+        let task = AsyncComputeTaskPool::get()
+            .spawn(async move { mesh_lod(&chunk, &neighbours, lod, packed) });
+        commands.entity(entity).insert(MeshTask(task));
+        return;
+    }
+    // Coarser neighbours don't line up with our voxels, keep our border faces
+    for dir in Direction::ALL {
+        let offset = dir.offset();
+        if voxel_world.lods.get(&(pos + offset)).is_some_and(|l| *l != lod) {
+            neighbours.clear(offset);
+        }
+    }
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { chunk.gen_meshes_with_neighbours(&neighbours, mode, packed) });
+    commands.entity(entity).insert(MeshTask(task));
+}
+
+// Queues the edited chunks and starts writing them once the last batch is done
+fn save_edited_chunks(
+    time: Res<Time>,
+    settings: Res<StreamingSettings>,
+    storage: Option<Res<ChunkStorage>>,
+    mut saves: ResMut<ChunkSaves>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    let Some(storage) = storage else {
+        voxel_world.unsaved.clear();
+        saves.queued.clear();
+        saves.features.clear();
+        return;
+    };
+    if let Some(task) = &mut saves.task {
+        if block_on(future::poll_once(task)).is_none() {
+            return;
+        }
+        saves.task = None;
+        saves.saving.clear();
+    }
+    saves.since_flush += time.delta_seconds();
+    if saves.since_flush < settings.save_interval {
+        return;
+    }
+    saves.since_flush = 0.;
+
+    let batch = saves.take_batch(&mut voxel_world);
+    if batch.0.is_empty() && batch.1.is_empty() {
+        return;
+    }
+    let storage = storage.clone();
+    saves.task = Some(IoTaskPool::get().spawn(async move { save_batch(&storage, &batch) }));
+}
+
+// Nothing edited is lost when the window is closed before the next batch
+fn save_on_exit(
+    exit: EventReader<AppExit>,
+    storage: Option<Res<ChunkStorage>>,
+    mut saves: ResMut<ChunkSaves>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    let Some(storage) = storage else {
+        return;
+    };
+    if exit.is_empty() {
+        return;
+    }
+    if let Some(task) = saves.task.take() {
+        block_on(task);
+    }
+    // Including the writes for chunks near the player
+    let features = voxel_world.take_feature_writes(|_| false);
+    saves.queue_features(features);
+    save_batch(&storage, &saves.take_batch(&mut voxel_world));
+}
+
+fn unload_chunks(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    mut saves: ResMut<ChunkSaves>,
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut last_box: Local<Option<(IVec3, IVec3)>>,
+    generating: Query<&ChunkEntity, With<GenerateTask>>,
+    camera_query: Query<&Transform, With<Camera3d>>,
+) {
+    let Some(center) = camera_chunk(&camera_query) else {
+        return;
+    };
+    // One chunk of margin so chunks on the border don't flicker in and out
+    let current = range_box(&settings, center, 1);
+    // Everything loaded was in range of the last box, nothing to do until it moves
+    let (min, max) = match last_box.replace(current) {
+        Some(last) if last == current => return,
+        Some(last) => last,
+        None => current,
+    };
+    // Chunks still generating aren't in the octree yet
+    let far: Vec<IVec3> = voxel_world
+        .octree
+        .chunks_in(min, max)
+        .into_iter()
+        .chain(generating.iter().map(|chunk| chunk.pos))
+        .filter(|pos| !in_range(&settings, center, *pos, 1))
+        .collect();
+    for pos in far {
+        let chunk = voxel_world.remove_chunk(pos);
+        if voxel_world.unsaved.remove(&pos) {
+            // Written with the next batch
+            if let Some(chunk) = chunk {
+                saves.queued.insert(pos, chunk);
+            }
+        }
+        if let Some(entity) = voxel_world.entities.remove(&pos) {
+            // Dropping the entity also cancels its pending tasks
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    // Writes for chunks out of range wait on disk until they're generated
+    let features = voxel_world.take_feature_writes(|pos| in_range(&settings, center, pos, 1));
+    saves.queue_features(features);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_chunks(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    storage: Option<Res<ChunkStorage>>,
+    saves: Res<ChunkSaves>,
+    generator: Res<ActiveGenerator>,
+    mut voxel_world: ResMut<VoxelWorld>,
+    generating: Query<&ChunkEntity, With<GenerateTask>>,
+    camera_query: Query<&Transform, With<Camera3d>>,
+) {
+    let Some(center) = camera_chunk(&camera_query) else {
+        return;
+    };
+    let (min, max) = range_box(&settings, center, 0);
+    let mut present: HashSet<IVec3> = voxel_world.octree.chunks_in(min, max).into_iter().collect();
+    present.extend(generating.iter().map(|chunk| chunk.pos));
+    let mut missing = Vec::new();
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let pos = IVec3::new(x, y, z);
+                // Chunks still being saved would load stale copies
+                if in_range(&settings, center, pos, 0)
+                    && !present.contains(&pos)
+                    && !saves.is_pending(pos)
+                {
+                    missing.push(pos);
+                }
+            }
+        }
+    }
+    // Closest chunks first
+    missing.sort_by_key(|pos| (*pos - center).length_squared());
+    missing.truncate(settings.chunks_per_frame);
+
+    let pool = AsyncComputeTaskPool::get();
+    for pos in missing {
+        let storage = storage.as_deref().cloned();
+        let generator = generator.0.clone();
+        let task = pool.spawn(async move {
+            // Saved chunks take priority over regenerating them
+            let saved = storage.as_ref().and_then(|storage| match storage.load_chunk(pos) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    error!("failed to load chunk {}: {}", pos, e);
+                    None
+                }
+            });
+            let (mut chunk, overflow) = match saved {
+                Some(chunk) => (chunk, None),
+                None => {
+                    let (mut chunk, overflow) = generator.generate_with_overflow(pos);
+                    // Parts of features rooted in chunks saved before this one
+                    // was generated
+                    match storage.map(|storage| storage.load_feature_writes(pos)) {
+                        Some(Ok(writes)) => {
+                            apply_writes(&mut chunk, &writes);
+                        }
+                        Some(Err(e)) => error!("failed to load feature writes {}: {}", pos, e),
+                        None => {}
+                    }
+                    (chunk, Some(overflow))
+                }
+            };
+            light_chunk(&mut chunk);
+            let summary = NodeSummary::of_chunk(&chunk);
+            (chunk, summary, overflow)
+        });
+        // Meshes are chunk local, the transform moves them into place
+        let transform = Transform::from_translation(chunk_origin(pos).as_vec3());
+        let entity = commands
+            .spawn((
+                GenerateTask(task),
+                ChunkEntity { pos },
+                SpatialBundle::from_transform(transform),
+            ))
+            .id();
+        voxel_world.entities.insert(pos, entity);
+    }
+}
+
+fn poll_generate_tasks(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut tasks: Query<(Entity, &mut GenerateTask)>,
+) {
+    let mut applied = 0;
+    for (entity, mut task) in &mut tasks {
+        if applied >= settings.results_per_frame {
+            break;
+        }
+        if let Some((mut chunk, summary, overflow)) = block_on(future::poll_once(&mut task.0)) {
+            commands.entity(entity).remove::<GenerateTask>();
+            let pos = chunk.position;
+            // Parts of trees rooted in neighbours generated before this chunk,
+            // saved chunks already hold theirs
+            let changed = match overflow {
+                Some(_) => voxel_world.apply_feature_writes(&mut chunk),
+                None => {
+                    voxel_world.feature_writes.remove(&pos);
+                    false
+                }
+            };
+            let summary = if changed {
+                light_chunk(&mut chunk);
+                NodeSummary::of_chunk(&chunk)
+            } else {
+                summary
+            };
+            // Marks the chunk and its neighbours for (re)meshing
+            voxel_world.add_chunk_with_summary(pos, chunk, summary);
+            voxel_world.stitch_light(pos);
+            if changed {
+                voxel_world.unsaved.insert(pos);
+            }
+            if let Some(overflow) = overflow {
+                voxel_world.fresh.insert(pos);
+                voxel_world.queue_feature_writes(pos, overflow);
+            }
+            applied += 1;
+        }
+    }
+}
+
+// Remeshes chunks whose level of detail changed, and their neighbours for the seams
+fn update_lods(
+    settings: Res<StreamingSettings>,
+    mut voxel_world: ResMut<VoxelWorld>,
+    camera_query: Query<&Transform, With<Camera3d>>,
+) {
+    let Some(center) = camera_chunk(&camera_query) else {
+        return;
+    };
+    // Loaded chunks are all within a chunk of the range
+    let (min, max) = range_box(&settings, center, 1);
+    let changed: Vec<(IVec3, u32)> = voxel_world
+        .octree
+        .chunks_in(min, max)
+        .into_iter()
+        .map(|pos| (pos, chunk_lod(&settings, center, pos)))
+        .filter(|(pos, lod)| voxel_world.lods.get(pos) != Some(lod))
+        .collect();
+    for (pos, lod) in changed {
+        voxel_world.lods.insert(pos, lod);
+        voxel_world.mark_dirty(pos);
+        voxel_world.mark_neighbours_dirty(pos);
+    }
+}
+
+fn remesh_dirty_chunks(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    let dirty: Vec<IVec3> = voxel_world.dirty.drain().collect();
+    for pos in dirty {
+        let Some(entity) = voxel_world.entities.get(&pos).copied() else {
+            continue;
+        };
+        // Replaces any mesh task still running for this chunk
+        spawn_mesh_task(
+            &mut commands,
+            &voxel_world,
+            entity,
+            pos,
+            settings.meshing_mode,
+            settings.packed_vertices,
+        );
+    }
+}
+
+// Replaces the bounds of the previous mesh. Packed meshes have no positions
+// to compute them from, they get the whole chunk.
+fn mesh_bounds(mesh: &Mesh) -> Aabb {
+    mesh.compute_aabb()
+        .unwrap_or_else(|| Aabb::from_min_max(Vec3::ZERO, Vec3::splat(CHUNK_SIZE as f32)))
+}
+
+fn poll_mesh_tasks(
+    mut commands: Commands,
+    settings: Res<StreamingSettings>,
+    material: Option<Res<ChunkMaterial>>,
+    transparent_material: Option<Res<TransparentChunkMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut tasks: Query<(Entity, &mut MeshTask)>,
+) {
+    let (Some(material), Some(transparent_material)) = (material, transparent_material) else {
+        return;
+    };
+    let mut applied = 0;
+    for (entity, mut task) in &mut tasks {
+        if applied >= settings.results_per_frame {
+            break;
+        }
+        let Some(chunk_meshes) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        let mut chunk_entity = commands.entity(entity);
+        // Not a whole bundle so the chunk keeps its transform
+        chunk_entity
+            .remove::<MeshTask>()
+            .insert((
+                mesh_bounds(&chunk_meshes.opaque),
+                meshes.add(chunk_meshes.opaque),
+                material.0.clone(),
+                ChunkMesh,
+            ))
+            // Drops the previous transparent mesh
+            .despawn_descendants();
+        if let Some(transparent) = chunk_meshes.transparent {
+            chunk_entity.with_children(|parent| {
+                parent.spawn((
+                    mesh_bounds(&transparent),
+                    MaterialMeshBundle {
+                        mesh: meshes.add(transparent),
+                        material: transparent_material.0.clone(),
+                        ..default()
+                    },
+                    TransparentChunkMesh,
+                ));
+            });
+        }
+        applied += 1;
+    }
+}
@@ -0,0 +1,221 @@
+use bevy::asset::{LoadState, LoadedFolder};
+use bevy::color::ColorToPacked;
+use bevy::pbr::{MaterialPipeline, MaterialPipelineKey};
+use bevy::prelude::*;
+use bevy::render::{
+    mesh::MeshVertexBufferLayoutRef,
+    render_asset::RenderAssetUsages,
+    render_resource::{
+        AsBindGroup, Extent3d, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+        TextureDimension, TextureFormat,
+    },
+    texture::ImageSampler,
+};
+
+use crate::block::{BlockId, BLOCK_COUNT};
+use crate::mesher::{ATLAS_COLUMNS, ATTRIBUTE_TILE};
+use crate::packed::ATTRIBUTE_PACKED;
+use crate::streaming::{ChunkMaterial, TransparentChunkMaterial};
+
+// Block textures are read from assets/blocks, named after the block with an
+// optional _top, _side or _bottom suffix, e.g. grass_top.png. Faces without a
+// texture are filled with the block color.
+pub const TEXTURE_DIR: &str = "blocks";
+// Width and height of every block texture
+pub const TILE_SIZE: u32 = 16;
+const SUFFIXES: [&str; ATLAS_COLUMNS as usize] = ["top", "side", "bottom"];
+
+// Chunk material, samples the atlas tile of each vertex and multiplies it
+// with the baked light in the vertex colors
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+#[bind_group_data(VoxelMaterialKey)]
+pub struct VoxelMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub atlas: Handle<Image>,
+    pub alpha_mode: AlphaMode,
+    // Draws back faces too, for water seen from below
+    pub double_sided: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoxelMaterialKey {
+    double_sided: bool,
+}
+
+impl From<&VoxelMaterial> for VoxelMaterialKey {
+    fn from(material: &VoxelMaterial) -> Self {
+        VoxelMaterialKey {
+            double_sided: material.double_sided,
+        }
+    }
+}
+
+impl Material for VoxelMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/voxel.wgsl".into()
+    }
+    fn fragment_shader() -> ShaderRef {
+        "shaders/voxel.wgsl".into()
+    }
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Same material for both vertex formats, the mesh decides
+        let vertex_layout = if layout.0.contains(ATTRIBUTE_PACKED) {
+            descriptor.vertex.shader_defs.push("PACKED_VERTICES".into());
+            if let Some(fragment) = &mut descriptor.fragment {
+                fragment.shader_defs.push("PACKED_VERTICES".into());
+            }
+            layout
+                .0
+                .get_layout(&[ATTRIBUTE_PACKED.at_shader_location(0)])?
+        } else {
+            layout.0.get_layout(&[
+                Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+                Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+                Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+                Mesh::ATTRIBUTE_COLOR.at_shader_location(3),
+                ATTRIBUTE_TILE.at_shader_location(4),
+            ])?
+        };
+        descriptor.vertex.buffers = vec![vertex_layout];
+        if key.bind_group_data.double_sided {
+            descriptor.primitive.cull_mode = None;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+struct BlockTextureFolder(Handle<LoadedFolder>);
+
+pub struct BlockTexturePlugin;
+impl Plugin for BlockTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<VoxelMaterial>::default())
+            .add_systems(Startup, load_block_textures)
+            .add_systems(
+                Update,
+                build_block_atlas.run_if(resource_exists::<BlockTextureFolder>),
+            );
+    }
+}
+
+fn load_block_textures(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BlockTextureFolder(asset_server.load_folder(TEXTURE_DIR)));
+}
+
+// Waits for the folder to load, then builds the atlas and the chunk materials
+fn build_block_atlas(
+    mut commands: Commands,
+    folder: Res<BlockTextureFolder>,
+    asset_server: Res<AssetServer>,
+    folders: Res<Assets<LoadedFolder>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<VoxelMaterial>>,
+) {
+    let textures: Vec<(String, Image)> = match asset_server.load_state(&folder.0) {
+        LoadState::Failed(_) => {
+            warn!("no block textures in assets/{}", TEXTURE_DIR);
+            Vec::new()
+        }
+        _ if !asset_server.is_loaded_with_dependencies(&folder.0) => return,
+        _ => folders
+            .get(&folder.0)
+            .map(|folder| {
+                folder
+                    .handles
+                    .iter()
+                    .filter_map(|handle| {
+                        let name = handle.path()?.path().file_stem()?.to_str()?.to_owned();
+                        let image = images.get(handle.id().try_typed::<Image>().ok()?)?;
+                        Some((name, image.clone()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    commands.remove_resource::<BlockTextureFolder>();
+
+    let atlas = images.add(build_atlas(&textures));
+    commands.insert_resource(ChunkMaterial(materials.add(VoxelMaterial {
+        atlas: atlas.clone(),
+        alpha_mode: AlphaMode::Opaque,
+        double_sided: false,
+    })));
+    commands.insert_resource(TransparentChunkMaterial(materials.add(VoxelMaterial {
+        atlas,
+        alpha_mode: AlphaMode::Blend,
+        double_sided: true,
+    })));
+}
+
+// ATLAS_COLUMNS x BLOCK_COUNT grid of tiles in BlockId order, see
+// mesher::texture_tile
+pub fn build_atlas(textures: &[(String, Image)]) -> Image {
+    let tile = TILE_SIZE as usize;
+    let width = tile * ATLAS_COLUMNS as usize;
+    let mut data = vec![0u8; width * tile * BLOCK_COUNT * 4];
+
+    for block in BlockId::ALL {
+        for (column, suffix) in SUFFIXES.iter().enumerate() {
+            let pixels = find_texture(textures, block, suffix).unwrap_or_else(|| {
+                let [r, g, b, a] = block.color();
+                let color = Srgba::from(LinearRgba::new(r, g, b, a));
+                color.to_u8_array().repeat(tile * tile)
+            });
+            let x0 = column * tile;
+            let y0 = block.texture() as usize * tile;
+            for row in 0..tile {
+                let start = ((y0 + row) * width + x0) * 4;
+                data[start..start + tile * 4]
+                    .copy_from_slice(&pixels[row * tile * 4..(row + 1) * tile * 4]);
+            }
+        }
+    }
+
+    let mut atlas = Image::new(
+        Extent3d {
+            width: width as u32,
+            height: (tile * BLOCK_COUNT) as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    atlas.sampler = ImageSampler::nearest();
+    atlas
+}
+
+// RGBA pixels of `name_suffix`, falling back to `name`
+fn find_texture(textures: &[(String, Image)], block: BlockId, suffix: &str) -> Option<Vec<u8>> {
+    let names = [
+        format!("{}_{}", block.name(), suffix),
+        block.name().to_owned(),
+    ];
+    let image = names
+        .iter()
+        .find_map(|name| textures.iter().find(|(n, _)| n == name))
+        .map(|(_, image)| image)?;
+    if image.width() != TILE_SIZE || image.height() != TILE_SIZE {
+        warn!(
+            "{} texture is {}x{}, expected {}x{}",
+            block.name(),
+            image.width(),
+            image.height(),
+            TILE_SIZE,
+            TILE_SIZE
+        );
+        return None;
+    }
+    Some(image.convert(TextureFormat::Rgba8UnormSrgb)?.data)
+}
@@ -1,7 +1,8 @@
 use bevy::math::f32::Vec3;
+use bevy::math::IVec3;
 use std::slice::Iter;
 
-#[derive(Clone,Copy)]
+#[derive(Clone,Copy,PartialEq,Eq,Hash,Debug)]
 pub enum Direction {
     North,
     South,
@@ -11,37 +12,87 @@ pub enum Direction {
     Down
 }
 
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn normal(self) -> Vec3 {
+        match self {
+            Direction::North => Vec3::X,
+            Direction::South => Vec3::NEG_X,
+            Direction::West => Vec3::Z,
+            Direction::East => Vec3::NEG_Z,
+            Direction::Up => Vec3::Y,
+            Direction::Down => Vec3::NEG_Y,
+        }
+    }
+    pub fn offset(self) -> IVec3 {
+        self.normal().as_ivec3()
+    }
+    // Index of the axis the face normal points along (x = 0, y = 1, z = 2)
+    pub fn axis(self) -> usize {
+        match self {
+            Direction::North | Direction::South => 0,
+            Direction::Up | Direction::Down => 1,
+            Direction::East | Direction::West => 2,
+        }
+    }
+    // Position in Direction::ALL
+    pub fn index(self) -> usize {
+        match self {
+            Direction::North => 0,
+            Direction::South => 1,
+            Direction::East => 2,
+            Direction::West => 3,
+            Direction::Up => 4,
+            Direction::Down => 5,
+        }
+    }
+}
+
 pub fn new_quad(dir: Direction, pos: Vec3) -> [[f32;3];4] {
+    new_rect(dir, pos, Vec3::ONE)
+}
+
+// Same as new_quad but spanning `size` voxels, the component along the
+// face normal should stay 1
+pub fn new_rect(dir: Direction, pos: Vec3, size: Vec3) -> [[f32;3];4] {
     //Down -y
     match dir {
         // Each face is written to have clockwise winding
         Direction::North => 
             [
-            [pos.x+1.,pos.y+0.,pos.z+0.],
-            [pos.x+1.,pos.y+1.,pos.z+0.],
-            [pos.x+1.,pos.y+1.,pos.z+1.],
-            [pos.x+1.,pos.y+0.,pos.z+1.],
+            [pos.x+size.x,pos.y+0.,pos.z+0.],
+            [pos.x+size.x,pos.y+size.y,pos.z+0.],
+            [pos.x+size.x,pos.y+size.y,pos.z+size.z],
+            [pos.x+size.x,pos.y+0.,pos.z+size.z],
             ],
         Direction::South => 
             [
             [pos.x+0.,pos.y+0.,pos.z+0.],
-            [pos.x+0.,pos.y+0.,pos.z+1.],
-            [pos.x+0.,pos.y+1.,pos.z+1.],
-            [pos.x+0.,pos.y+1.,pos.z+0.],
+            [pos.x+0.,pos.y+0.,pos.z+size.z],
+            [pos.x+0.,pos.y+size.y,pos.z+size.z],
+            [pos.x+0.,pos.y+size.y,pos.z+0.],
             ],
         Direction::East => 
             [
-            [pos.x+1.,pos.y+0.,pos.z+0.],
+            [pos.x+size.x,pos.y+0.,pos.z+0.],
             [pos.x+0.,pos.y+0.,pos.z+0.],
-            [pos.x+0.,pos.y+1.,pos.z+0.],
-            [pos.x+1.,pos.y+1.,pos.z+0.],
+            [pos.x+0.,pos.y+size.y,pos.z+0.],
+            [pos.x+size.x,pos.y+size.y,pos.z+0.],
             ],
         Direction::West => 
             [
-            [pos.x+1.,pos.y+0.,pos.z+1.],
-            [pos.x+1.,pos.y+1.,pos.z+1.],
-            [pos.x+0.,pos.y+1.,pos.z+1.],
-            [pos.x+0.,pos.y+0.,pos.z+1.],
+            [pos.x+size.x,pos.y+0.,pos.z+size.z],
+            [pos.x+size.x,pos.y+size.y,pos.z+size.z],
+            [pos.x+0.,pos.y+size.y,pos.z+size.z],
+            [pos.x+0.,pos.y+0.,pos.z+size.z],
             //[pos.x+0.9,pos.y+0.1,pos.z+0.9],
             //[pos.x+0.9,pos.y+0.9,pos.z+0.9],
             //[pos.x+0.1,pos.y+0.9,pos.z+0.9],
@@ -49,17 +100,17 @@ pub fn new_quad(dir: Direction, pos: Vec3) -> [[f32;3];4] {
             ],
         Direction::Up => 
             [
-            [pos.x+0.,pos.y+1.,pos.z+0.],
-            [pos.x+0.,pos.y+1.,pos.z+1.],
-            [pos.x+1.,pos.y+1.,pos.z+1.],
-            [pos.x+1.,pos.y+1.,pos.z+0.],
+            [pos.x+0.,pos.y+size.y,pos.z+0.],
+            [pos.x+0.,pos.y+size.y,pos.z+size.z],
+            [pos.x+size.x,pos.y+size.y,pos.z+size.z],
+            [pos.x+size.x,pos.y+size.y,pos.z+0.],
             ],
         Direction::Down => 
             [
             [pos.x+0.,pos.y+0.,pos.z+0.],
-            [pos.x+1.,pos.y+0.,pos.z+0.],
-            [pos.x+1.,pos.y+0.,pos.z+1.],
-            [pos.x+0.,pos.y+0.,pos.z+1.],
+            [pos.x+size.x,pos.y+0.,pos.z+0.],
+            [pos.x+size.x,pos.y+0.,pos.z+size.z],
+            [pos.x+0.,pos.y+0.,pos.z+size.z],
             ],
     }
 }
@@ -0,0 +1,89 @@
+use crate::block::BlockId;
+use crate::quad::Direction;
+use crate::world::VoxelWorld;
+
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub pos: IVec3,
+    pub block: BlockId,
+    // Face of the voxel the ray entered through
+    pub face: Direction,
+    pub distance: f32,
+}
+
+// Face entered when stepping along `axis` in the `step` direction
+fn entered_face(axis: usize, step: i32) -> Direction {
+    match (axis, step > 0) {
+        (0, true) => Direction::South,
+        (0, false) => Direction::North,
+        (1, true) => Direction::Down,
+        (1, false) => Direction::Up,
+        (_, true) => Direction::East,
+        (_, false) => Direction::West,
+    }
+}
+
+fn min_axis(v: Vec3) -> usize {
+    if v.x <= v.y && v.x <= v.z {
+        0
+    } else if v.y <= v.z {
+        1
+    } else {
+        2
+    }
+}
+
+impl VoxelWorld {
+    // Amanatides & Woo voxel traversal, stops at the first solid voxel.
+    // Unloaded chunks are treated as air. Rays need a finite origin and
+    // max_distance, the walk only ends on a hit or past max_distance.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        let dir = direction.normalize_or_zero();
+        if dir == Vec3::ZERO || !origin.is_finite() || !max_distance.is_finite() {
+            return None;
+        }
+        let mut voxel = origin.floor().as_ivec3();
+        let mut step = IVec3::ZERO;
+        let mut t_max = Vec3::INFINITY;
+        let mut t_delta = Vec3::INFINITY;
+        for axis in 0..3 {
+            if dir[axis] > 0. {
+                step[axis] = 1;
+                t_max[axis] = (voxel[axis] as f32 + 1. - origin[axis]) / dir[axis];
+            } else if dir[axis] < 0. {
+                step[axis] = -1;
+                t_max[axis] = (origin[axis] - voxel[axis] as f32) / -dir[axis];
+            } else {
+                continue;
+            }
+            t_delta[axis] = 1. / dir[axis].abs();
+        }
+
+        // Starting inside a block reports the face the ray points away from
+        let main_axis = min_axis(-dir.abs());
+        let mut face = entered_face(main_axis, step[main_axis]);
+        let mut distance = 0.;
+        loop {
+            if let Some(block) = self.get_voxel(voxel) {
+                if block.is_solid() {
+                    return Some(RaycastHit {
+                        pos: voxel,
+                        block,
+                        face,
+                        distance,
+                    });
+                }
+            }
+            let axis = min_axis(t_max);
+            distance = t_max[axis];
+            if distance > max_distance {
+                return None;
+            }
+            voxel[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            face = entered_face(axis, step[axis]);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::block::BlockId;
+use crate::chunk::*;
+use crate::features::FeatureWrite;
+use crate::world::{chunk_origin, world_to_chunk};
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+// Region files hold REGION_SIZE x REGION_SIZE chunks of one chunk layer (same y).
+//
+// Layout, little endian:
+//   magic         4 bytes  "BCRG"
+//   offset table  REGION_SIZE^2 entries of (offset: u32, length: u32),
+//                 indexed x * REGION_SIZE + z, length 0 means not stored
+//   chunk blobs   run-length encoded block ids, see compress()
+//
+// Feature blocks waiting for a chunk that was never generated go in a
+// .features file with the same layout, see features_to_bytes()
+pub const REGION_SIZE: i32 = 16;
+const MAGIC: &[u8; 4] = b"BCRG";
+const TABLE_LEN: usize = (REGION_SIZE * REGION_SIZE) as usize;
+const HEADER_LEN: usize = MAGIC.len() + TABLE_LEN * 8;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Resource, Clone)]
+pub struct ChunkStorage {
+    dir: PathBuf,
+}
+
+impl ChunkStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ChunkStorage { dir: dir.into() }
+    }
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Region file and table index of a chunk, `kind` is the file extension
+    fn locate(&self, chunk_pos: IVec3, kind: &str) -> (PathBuf, usize) {
+        let region = IVec3::new(
+            chunk_pos.x.div_euclid(REGION_SIZE),
+            chunk_pos.y,
+            chunk_pos.z.div_euclid(REGION_SIZE),
+        );
+        let local_x = chunk_pos.x.rem_euclid(REGION_SIZE);
+        let local_z = chunk_pos.z.rem_euclid(REGION_SIZE);
+        let file = format!("r.{}.{}.{}.{}", region.x, region.y, region.z, kind);
+        (self.dir.join(file), (local_x * REGION_SIZE + local_z) as usize)
+    }
+
+    fn load_entry(&self, chunk_pos: IVec3, kind: &str) -> io::Result<Option<Vec<u8>>> {
+        let (path, index) = self.locate(chunk_pos, kind);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(read_region(&bytes)?.swap_remove(index))
+    }
+
+    // One read and one write per region file, however many of its entries
+    // `update` changes
+    fn update_entries<T>(
+        &self,
+        kind: &str,
+        items: impl IntoIterator<Item = (IVec3, T)>,
+        mut update: impl FnMut(&mut Option<Vec<u8>>, T),
+    ) -> io::Result<()> {
+        let mut regions: HashMap<PathBuf, Vec<(usize, T)>> = HashMap::new();
+        for (chunk_pos, item) in items {
+            let (path, index) = self.locate(chunk_pos, kind);
+            regions.entry(path).or_default().push((index, item));
+        }
+        if regions.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        for (path, items) in regions {
+            let mut region = match fs::read(&path) {
+                Ok(bytes) => read_region(&bytes)?,
+                Err(e) if e.kind() == ErrorKind::NotFound => vec![None; TABLE_LEN],
+                Err(e) => return Err(e),
+            };
+            for (index, item) in items {
+                update(&mut region[index], item);
+            }
+            // Written next to the region and renamed so readers never see half a file
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, write_region(&region))?;
+            fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+
+    // Ok(None) if the chunk was never saved
+    pub fn load_chunk(&self, chunk_pos: IVec3) -> io::Result<Option<Chunk>> {
+        match self.load_entry(chunk_pos, "region")? {
+            Some(blob) => Ok(Some(chunk_from_bytes(chunk_pos, &decompress(&blob)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_chunk(&self, chunk: &Chunk) -> io::Result<()> {
+        self.save_chunks([chunk])
+    }
+
+    pub fn save_chunks<'a>(&self, chunks: impl IntoIterator<Item = &'a Chunk>) -> io::Result<()> {
+        let chunks = chunks.into_iter().map(|chunk| (chunk.position, chunk));
+        self.update_entries("region", chunks, |entry, chunk| {
+            *entry = Some(compress(&chunk_to_bytes(chunk)));
+        })
+    }
+
+    // Feature blocks saved for a chunk that wasn't generated yet
+    pub fn load_feature_writes(&self, chunk_pos: IVec3) -> io::Result<Vec<FeatureWrite>> {
+        match self.load_entry(chunk_pos, "features")? {
+            Some(bytes) => features_from_bytes(chunk_pos, &bytes),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // Added to the writes already saved for each chunk
+    pub fn save_feature_writes<'a>(
+        &self,
+        writes: impl IntoIterator<Item = (IVec3, &'a [FeatureWrite])>,
+    ) -> io::Result<()> {
+        self.update_entries("features", writes, |entry, writes| {
+            let bytes = entry.get_or_insert_with(Vec::new);
+            bytes.extend(features_to_bytes(writes));
+            // A root generated again queues the same writes again. Their order
+            // doesn't matter, logs replace leaves either way.
+            let mut writes: Vec<&[u8]> = bytes.chunks(3).collect();
+            writes.sort_unstable();
+            writes.dedup();
+            *bytes = writes.concat();
+        })
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_region(bytes: &[u8]) -> io::Result<Vec<Option<Vec<u8>>>> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(invalid("not a region file"));
+    }
+    let mut chunks = Vec::with_capacity(TABLE_LEN);
+    for i in 0..TABLE_LEN {
+        let entry = MAGIC.len() + i * 8;
+        let offset = read_u32(bytes, entry) as usize;
+        let len = read_u32(bytes, entry + 4) as usize;
+        if len == 0 {
+            chunks.push(None);
+            continue;
+        }
+        let blob = bytes
+            .get(offset..offset + len)
+            .ok_or_else(|| invalid("chunk outside of region file"))?;
+        chunks.push(Some(blob.to_vec()));
+    }
+    Ok(chunks)
+}
+
+fn write_region(chunks: &[Option<Vec<u8>>]) -> Vec<u8> {
+    let mut table = Vec::with_capacity(HEADER_LEN);
+    let mut data = Vec::new();
+    table.extend_from_slice(MAGIC);
+    for chunk in chunks {
+        let (offset, len) = match chunk {
+            Some(blob) => {
+                let offset = HEADER_LEN + data.len();
+                data.extend_from_slice(blob);
+                (offset, blob.len())
+            }
+            None => (0, 0),
+        };
+        table.extend((offset as u32).to_le_bytes());
+        table.extend((len as u32).to_le_bytes());
+    }
+    table.extend(data);
+    table
+}
+
+// Block ids in x, y, z order
+pub fn chunk_to_bytes(chunk: &Chunk) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(CHUNK_VOLUME);
+    for x in 0..32usize {
+        for y in 0..32usize {
+            for z in 0..32usize {
+                bytes.push(chunk.data.get(x, y, z) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+pub fn chunk_from_bytes(chunk_pos: IVec3, bytes: &[u8]) -> io::Result<Chunk> {
+    if bytes.len() != CHUNK_VOLUME {
+        return Err(invalid("wrong chunk size"));
+    }
+    let mut chunk = Chunk::new(chunk_pos);
+    let mut bytes = bytes.iter();
+    for x in 0..32usize {
+        for y in 0..32usize {
+            for z in 0..32usize {
+                let id = *bytes.next().unwrap();
+                let block = BlockId::from_u8(id).ok_or_else(|| invalid("unknown block id"))?;
+                chunk.data.set(x, y, z, block);
+            }
+        }
+    }
+    chunk.data.compact();
+    Ok(chunk)
+}
+
+// (index: u16, block: u8) per write, the index is local to the chunk in the
+// same x, y, z order as chunk_to_bytes
+pub fn features_to_bytes(writes: &[FeatureWrite]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(writes.len() * 3);
+    for write in writes {
+        let (_, local) = world_to_chunk(write.pos);
+        let index = (local.x * CHUNK_SIZE + local.y) * CHUNK_SIZE + local.z;
+        bytes.extend((index as u16).to_le_bytes());
+        bytes.push(write.block as u8);
+    }
+    bytes
+}
+
+pub fn features_from_bytes(chunk_pos: IVec3, bytes: &[u8]) -> io::Result<Vec<FeatureWrite>> {
+    if bytes.len() % 3 != 0 {
+        return Err(invalid("truncated feature write"));
+    }
+    let mut writes = Vec::with_capacity(bytes.len() / 3);
+    for write in bytes.chunks(3) {
+        let index = u16::from_le_bytes([write[0], write[1]]) as i32;
+        if index as usize >= CHUNK_VOLUME {
+            return Err(invalid("feature write outside of chunk"));
+        }
+        let local = IVec3::new(
+            index / (CHUNK_SIZE * CHUNK_SIZE),
+            index / CHUNK_SIZE % CHUNK_SIZE,
+            index % CHUNK_SIZE,
+        );
+        writes.push(FeatureWrite {
+            pos: chunk_origin(chunk_pos) + local,
+            block: BlockId::from_u8(write[2]).ok_or_else(|| invalid("unknown block id"))?,
+        });
+    }
+    Ok(writes)
+}
+
+// Runs of (length: u16, value: u8)
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let value = bytes[i];
+        let mut run = 1;
+        while i + run < bytes.len() && bytes[i + run] == value && run < u16::MAX as usize {
+            run += 1;
+        }
+        out.extend((run as u16).to_le_bytes());
+        out.push(value);
+        i += run;
+    }
+    out
+}
+
+pub fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    if bytes.len() % 3 != 0 {
+        return Err(invalid("truncated run"));
+    }
+    let mut out = Vec::with_capacity(CHUNK_VOLUME);
+    for run in bytes.chunks(3) {
+        let len = u16::from_le_bytes([run[0], run[1]]) as usize;
+        out.extend(std::iter::repeat(run[2]).take(len));
+    }
+    Ok(out)
+}
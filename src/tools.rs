@@ -6,7 +6,10 @@ macro_rules! implToUsizeSigned {
     ($($t:ty),*) => {$(
             impl ToUsize for $t {
                 fn to_usize(self) -> usize {
-                    self.abs() as usize
+                    // Negative values used to be mirrored with abs(), use
+                    // world_to_chunk for world coordinates instead
+                    debug_assert!(self >= 0, "negative index {}", self);
+                    self as usize
                 }
     })*};
 }
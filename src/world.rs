@@ -1,15 +1,80 @@
+use crate::block::BlockId;
 use crate::chunk::*;
-use crate::quad::Direction;
+use crate::features::{apply_writes, FeatureWrite};
+use crate::octree::{ChunkOctree, NodeSummary};
 use bevy::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 #[derive(Resource, Default)]
 pub struct VoxelWorld {
     pub chunks: HashMap<IVec3, Arc<Chunk>>,
+    // Mesh entity spawned for each chunk
+    pub entities: HashMap<IVec3, Entity>,
+    // Chunks whose mesh is out of date
+    pub dirty: HashSet<IVec3>,
+    // Edited chunks not written to disk yet
+    pub unsaved: HashSet<IVec3>,
+    // Drained into `VoxelChanged` events by VoxelWorldPlugin
+    pub changes: Vec<VoxelChanged>,
+    // Feature blocks waiting for their chunk to be generated. Those far from
+    // the loaded chunks are taken out with take_feature_writes and saved.
+    pub feature_writes: HashMap<IVec3, Vec<FeatureWrite>>,
+    // Loaded root chunks whose features reaching into neighbours were queued,
+    // so a regenerated root doesn't write them again
+    pub features_placed: HashSet<IVec3>,
+    // Generated chunks that weren't loaded from storage or edited since, the
+    // only loaded chunks features write into
+    pub fresh: HashSet<IVec3>,
+    // Level of detail each chunk is meshed at, see lod.rs
+    pub lods: HashMap<IVec3, u32>,
+    // Summaries of the loaded chunks for range queries and far away regions
+    pub octree: ChunkOctree,
     pub quads: u64,
 }
 
+#[derive(Event, Clone, Copy, Debug)]
+pub struct VoxelChanged {
+    pub pos: IVec3,
+    pub old: BlockId,
+    pub new: BlockId,
+}
+
+// Put on the entity of each chunk, the reverse of VoxelWorld::entities
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ChunkEntity {
+    pub pos: IVec3,
+}
+
+pub struct VoxelWorldPlugin;
+impl Plugin for VoxelWorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VoxelWorld::new())
+            .add_event::<VoxelChanged>()
+            .add_systems(Last, send_voxel_events);
+    }
+}
+
+fn send_voxel_events(
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut events: EventWriter<VoxelChanged>,
+) {
+    if !voxel_world.changes.is_empty() {
+        events.send_batch(voxel_world.changes.drain(..));
+    }
+}
+
+// World position of a chunk's origin voxel
+pub fn chunk_origin(chunk_pos: IVec3) -> IVec3 {
+    chunk_pos * CHUNK_SIZE
+}
+
+// Splits a world voxel position into the chunk position and the position inside it
+pub fn world_to_chunk(world_pos: IVec3) -> (IVec3, IVec3) {
+    let size = IVec3::splat(CHUNK_SIZE);
+    (world_pos.div_euclid(size), world_pos.rem_euclid(size))
+}
+
 impl VoxelWorld {
     pub fn new() -> Self {
         VoxelWorld {
@@ -18,101 +83,171 @@ impl VoxelWorld {
         }
     }
     pub fn add_chunk(&mut self, pos: IVec3, chunk: Chunk) {
+        let summary = NodeSummary::of_chunk(&chunk);
+        self.add_chunk_with_summary(pos, chunk, summary);
+    }
+    // Takes a summary computed ahead of time, off the main thread
+    pub fn add_chunk_with_summary(&mut self, pos: IVec3, chunk: Chunk, summary: NodeSummary) {
+        self.octree.insert(pos, summary);
         self.chunks.insert(pos, chunk.into());
+        self.dirty.insert(pos);
+        self.mark_neighbours_dirty(pos);
     }
-    pub fn get_chunk(&self, pos: IVec3) -> Option<Arc<Chunk>> {
-        match self.chunks.get(&pos) {
-            Some(c) => Some(c.clone()),
-            None => None,
+    pub fn remove_chunk(&mut self, pos: IVec3) -> Option<Arc<Chunk>> {
+        let chunk = self.chunks.remove(&pos);
+        self.dirty.remove(&pos);
+        self.fresh.remove(&pos);
+        self.features_placed.remove(&pos);
+        self.lods.remove(&pos);
+        self.octree.remove(pos);
+        if chunk.is_some() {
+            self.mark_neighbours_dirty(pos);
         }
+        chunk
     }
-
-    //TODO Simplyfy (put middle chunkl in ChunkNeighbours and impl get_block(x,y,z) for ChunkNeighbours
-    pub fn get_voxel_neighbours(
-        &self,
-        chunk_data: &ChunkData,
-        neighbours: &ChunkNeighbours,
-        voxel_pos: IVec3,
-    ) -> Vec<Direction> {
-        let mut directions: Vec<Direction> = Vec::new();
-        if voxel_pos.x == 0 {
-            if let Some(chunk) = neighbours.get(IVec3::NEG_X) {
-                if !chunk.data.get(31, voxel_pos.y, voxel_pos.z) {
-                    directions.push(Direction::South);
-                }
-            } else {
-                directions.push(Direction::South);
-            }
-        } else if voxel_pos.x == CHUNK_SIZE - 1 {
-            if let Some(chunk) = neighbours.get(IVec3::X) {
-                if !chunk.data.get(0, voxel_pos.y, voxel_pos.z) {
-                    directions.push(Direction::North);
-                }
-            } else {
-                directions.push(Direction::North);
-            }
-        }
-        if voxel_pos.x != 0 && !chunk_data.get(voxel_pos.x - 1, voxel_pos.y, voxel_pos.z) {
-            directions.push(Direction::South)
-        }
-        if voxel_pos.x != CHUNK_SIZE - 1
-            && !chunk_data.get(voxel_pos.x + 1, voxel_pos.y, voxel_pos.z)
-        {
-            directions.push(Direction::North)
+    pub fn mark_dirty(&mut self, pos: IVec3) {
+        if self.chunks.contains_key(&pos) {
+            self.dirty.insert(pos);
         }
-
-        if voxel_pos.y == 0 {
-            if let Some(chunk) = neighbours.get(IVec3::NEG_Y) {
-                if !chunk.data.get(voxel_pos.x, 31, voxel_pos.z) {
-                    directions.push(Direction::Down)
+    }
+    // Neighbours cull border faces and sample ambient occlusion across edges
+    // and corners against this chunk
+    pub fn mark_neighbours_dirty(&mut self, pos: IVec3) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let offset = IVec3::new(x, y, z);
+                    if offset != IVec3::ZERO {
+                        self.mark_dirty(pos + offset);
+                    }
                 }
-            } else {
-                //directions.push(Direction::Down) //Remove world floor
             }
-        } else if voxel_pos.y == CHUNK_SIZE - 1 {
-            if let Some(chunk) = neighbours.get(IVec3::Y) {
-                if !chunk.data.get(voxel_pos.x, 0, voxel_pos.z) {
-                    directions.push(Direction::Up)
+        }
+    }
+    // The chunk and the neighbours whose border faces or ambient occlusion
+    // touch the voxel, including edge and corner neighbours
+    pub fn mark_voxel_dirty(&mut self, chunk_pos: IVec3, local: IVec3) {
+        // Offsets towards the borders the voxel sits on, 0 on the others
+        let step = |i: i32| match i {
+            0 => -1,
+            i if i == CHUNK_SIZE - 1 => 1,
+            _ => 0,
+        };
+        let border = IVec3::new(step(local.x), step(local.y), step(local.z));
+        for x in [0, border.x] {
+            for y in [0, border.y] {
+                for z in [0, border.z] {
+                    self.mark_dirty(chunk_pos + IVec3::new(x, y, z));
                 }
-            } else {
-                directions.push(Direction::Up)
             }
         }
-        if voxel_pos.y != 0 && !chunk_data.get(voxel_pos.x, voxel_pos.y - 1, voxel_pos.z) {
-            directions.push(Direction::Down)
+    }
+    // Loaded chunk positions in the box from `min` to `max`, inclusive
+    pub fn chunks_in(&self, min: IVec3, max: IVec3) -> Vec<IVec3> {
+        self.octree.chunks_in(min, max)
+    }
+    // Keeps the octree summary of a chunk in step with a voxel edit
+    fn reindex_voxel(&mut self, chunk_pos: IVec3, old: BlockId, new: BlockId) {
+        let Some(mut summary) = self.octree.summary(chunk_pos, 0) else {
+            return;
+        };
+        summary.solid_voxels = summary.solid_voxels + new.is_solid() as u64 - old.is_solid() as u64;
+        summary.uniform = self.chunks.get(&chunk_pos).and_then(|chunk| chunk.data.single_block());
+        self.octree.insert(chunk_pos, summary);
+    }
+    pub fn get_chunk(&self, pos: IVec3) -> Option<Arc<Chunk>> {
+        match self.chunks.get(&pos) {
+            Some(c) => Some(c.clone()),
+            None => None,
         }
-        if voxel_pos.y != CHUNK_SIZE - 1
-            && !chunk_data.get(voxel_pos.x, voxel_pos.y + 1, voxel_pos.z)
-        {
-            directions.push(Direction::Up)
+    }
+    // Bytes used by block and light storage of all loaded chunks
+    pub fn memory_usage(&self) -> usize {
+        self.chunks
+            .values()
+            .map(|chunk| chunk.data.memory_usage() + chunk.light.memory_usage())
+            .sum()
+    }
+    // Writes the overflow of a freshly generated root chunk into fresh loaded
+    // chunks right away, the rest waits for apply_feature_writes. Only the
+    // first call for each root does anything.
+    pub fn queue_feature_writes(&mut self, root: IVec3, writes: Vec<FeatureWrite>) {
+        if !self.features_placed.insert(root) {
+            return;
         }
-
-        if voxel_pos.z == 0 {
-            if let Some(chunk) = neighbours.get(IVec3::NEG_Z) {
-                if !chunk.data.get(voxel_pos.x, voxel_pos.y, 31) {
-                    directions.push(Direction::East)
-                }
-            } else {
-                directions.push(Direction::East)
+        for write in writes {
+            let (chunk_pos, local) = world_to_chunk(write.pos);
+            let Some(chunk) = self.chunks.get_mut(&chunk_pos) else {
+                self.feature_writes.entry(chunk_pos).or_default().push(write);
+                continue;
+            };
+            // Saved and edited chunks keep what they have
+            if !self.fresh.contains(&chunk_pos) {
+                continue;
             }
-        } else if voxel_pos.z == CHUNK_SIZE - 1 {
-            if let Some(chunk) = neighbours.get(IVec3::Z) {
-                if !chunk.data.get(voxel_pos.x, voxel_pos.y, 0) {
-                    directions.push(Direction::West)
-                }
-            } else {
-                directions.push(Direction::West)
+            let old = chunk.data.get(local.x, local.y, local.z);
+            if !write.applies_to(old) {
+                continue;
             }
+            Arc::make_mut(chunk)
+                .data
+                .set(local.x, local.y, local.z, write.block);
+            self.mark_dirty(chunk_pos);
+            self.unsaved.insert(chunk_pos);
+            self.reindex_voxel(chunk_pos, old, write.block);
+            self.update_light(write.pos, write.block);
         }
-        if voxel_pos.z != 0 && !chunk_data.get(voxel_pos.x, voxel_pos.y, voxel_pos.z - 1) {
-            directions.push(Direction::East)
+    }
+    // Applies and forgets the writes queued for a freshly generated chunk
+    // before it's added, true if any block was written
+    pub fn apply_feature_writes(&mut self, chunk: &mut Chunk) -> bool {
+        match self.feature_writes.remove(&chunk.position) {
+            Some(writes) => apply_writes(chunk, &writes),
+            None => false,
         }
-        if voxel_pos.z != CHUNK_SIZE - 1
-            && !chunk_data.get(voxel_pos.x, voxel_pos.y, voxel_pos.z + 1)
-        {
-            directions.push(Direction::West)
+    }
+    // Removes the queued writes for chunks `keep` returns false for
+    pub fn take_feature_writes(
+        &mut self,
+        keep: impl Fn(IVec3) -> bool,
+    ) -> Vec<(IVec3, Vec<FeatureWrite>)> {
+        let far: Vec<IVec3> = self
+            .feature_writes
+            .keys()
+            .filter(|pos| !keep(**pos))
+            .copied()
+            .collect();
+        far.into_iter()
+            .filter_map(|pos| Some((pos, self.feature_writes.remove(&pos)?)))
+            .collect()
+    }
+    // None if the chunk containing `world_pos` isn't loaded
+    pub fn get_voxel(&self, world_pos: IVec3) -> Option<BlockId> {
+        let (chunk_pos, local) = world_to_chunk(world_pos);
+        let chunk = self.chunks.get(&chunk_pos)?;
+        Some(chunk.data.get(local.x, local.y, local.z))
+    }
+    // Returns the previous block, None if the chunk isn't loaded
+    pub fn set_voxel(&mut self, world_pos: IVec3, block: BlockId) -> Option<BlockId> {
+        let (chunk_pos, local) = world_to_chunk(world_pos);
+        let chunk = self.chunks.get_mut(&chunk_pos)?;
+        let old = chunk.data.get(local.x, local.y, local.z);
+        if old == block {
+            return Some(old);
         }
+        // Copies the chunk if a mesh task still holds it
+        Arc::make_mut(chunk).data.set(local.x, local.y, local.z, block);
 
-        return directions;
+        self.mark_voxel_dirty(chunk_pos, local);
+        self.unsaved.insert(chunk_pos);
+        self.fresh.remove(&chunk_pos);
+        self.reindex_voxel(chunk_pos, old, block);
+        self.update_light(world_pos, block);
+        self.changes.push(VoxelChanged {
+            pos: world_pos,
+            old,
+            new: block,
+        });
+        Some(old)
     }
 }
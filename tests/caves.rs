@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy_cubes::biome::BiomeGenerator;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::caves::CaveGenerator;
+use bevy_cubes::generator::{GeneratorConfig, WorldGenerator};
+use bevy_cubes::region::chunk_to_bytes;
+
+// Chunks from the surface down into solid rock
+fn positions() -> Vec<IVec3> {
+    let mut positions = Vec::new();
+    for x in 0..2 {
+        for y in -3..2 {
+            positions.push(IVec3::new(x, y, 1));
+        }
+    }
+    positions
+}
+
+// Counts voxels the cave generator removed from the biome terrain, checking
+// it never removes anything shallower than `cave_min_depth` and only adds
+// overhangs above the surface
+fn carved(config: &GeneratorConfig) -> usize {
+    let terrain = BiomeGenerator::new(config.clone());
+    let caves = CaveGenerator::new(config.clone());
+    let mut carved = 0;
+    for pos in positions() {
+        let mut data = [[[BlockId::Air; 32]; 32]; 32];
+        let heights = terrain.fill_terrain(pos, &mut data);
+        let chunk = caves.generate(pos);
+        for (x, plane) in data.iter().enumerate() {
+            for (y, row) in plane.iter().enumerate() {
+                for (z, &before) in row.iter().enumerate() {
+                    let after = chunk.data.get(x, y, z);
+                    if before == after {
+                        continue;
+                    }
+                    let depth = heights[x][z] - (pos.y * 32 + y as i32) as f32;
+                    if !before.is_solid() {
+                        assert_eq!((before, after), (BlockId::Air, BlockId::Stone));
+                        assert!(depth < 0., "overhang {} below the surface", depth);
+                        continue;
+                    }
+                    assert_eq!(after, BlockId::Air);
+                    assert!(
+                        depth >= config.cave_min_depth,
+                        "carved {} below the surface",
+                        depth
+                    );
+                    carved += 1;
+                }
+            }
+        }
+    }
+    carved
+}
+
+#[test]
+fn caves_carve_below_min_depth() {
+    let config = GeneratorConfig {
+        cave_density: 1.,
+        ..default()
+    };
+    assert!(carved(&config) > 0);
+
+    let deeper = GeneratorConfig {
+        cave_min_depth: 40.,
+        ..config.clone()
+    };
+    assert!(carved(&deeper) < carved(&config));
+}
+
+#[test]
+fn zero_density_has_no_caves() {
+    let config = GeneratorConfig {
+        cave_density: 0.,
+        ..default()
+    };
+    assert_eq!(carved(&config), 0);
+
+    let terrain = BiomeGenerator::new(config.clone());
+    let caves = CaveGenerator::new(config);
+    for pos in positions() {
+        assert!(!caves.is_cave(pos * 32));
+        assert_eq!(
+            chunk_to_bytes(&caves.generate(pos)),
+            chunk_to_bytes(&terrain.generate(pos))
+        );
+    }
+}
+
+#[test]
+fn overhangs_above_the_surface() {
+    let config = GeneratorConfig::default();
+    let terrain = BiomeGenerator::new(config.clone());
+    let caves = CaveGenerator::new(config.clone());
+    // Air voxels above the surface with rock further up the same column
+    let mut overhangs = 0;
+    for x in -2..2 {
+        for z in -2..2 {
+            for y in -2..4 {
+                let pos = IVec3::new(x, y, z);
+                let mut data = [[[BlockId::Air; 32]; 32]; 32];
+                let heights = terrain.fill_terrain(pos, &mut data);
+                let chunk = caves.generate(pos);
+                for (x, column) in heights.iter().enumerate() {
+                    for (z, height) in column.iter().enumerate() {
+                        let mut roof = false;
+                        for y in (0..32).rev() {
+                            let solid = chunk.data.get(x, y, z).is_solid();
+                            let above_surface = (pos.y * 32 + y as i32) as f32 > *height;
+                            if above_surface && roof && !solid {
+                                overhangs += 1;
+                            }
+                            roof |= solid;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    assert!(overhangs > 0);
+
+    let flat = GeneratorConfig {
+        overhang_height: 0.,
+        ..config
+    };
+    let caves = CaveGenerator::new(flat);
+    assert!(!caves.is_overhang(IVec3::new(3, 40, 5), 1.));
+}
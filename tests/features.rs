@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::features::FeatureWrite;
+use bevy_cubes::generator::{GeneratorConfig, WorldGenerator};
+use bevy_cubes::region::chunk_to_bytes;
+use bevy_cubes::world::{world_to_chunk, VoxelWorld};
+use std::sync::Arc;
+
+fn generator() -> Arc<dyn WorldGenerator> {
+    GeneratorConfig {
+        tree_chance: 0.02,
+        ..default()
+    }
+    .build()
+}
+
+// Same steps as poll_generate_tasks for a freshly generated chunk
+fn generate(world: &mut VoxelWorld, generator: &dyn WorldGenerator, pos: IVec3) {
+    let (mut chunk, overflow) = generator.generate_with_overflow(pos);
+    let changed = world.apply_feature_writes(&mut chunk);
+    world.add_chunk(pos, chunk);
+    world.fresh.insert(pos);
+    if changed {
+        world.unsaved.insert(pos);
+    }
+    world.queue_feature_writes(pos, overflow);
+}
+
+// A root chunk with a feature block that lands in air of another chunk
+fn straddling(generator: &dyn WorldGenerator) -> (IVec3, FeatureWrite) {
+    for x in 0..8 {
+        for z in 0..8 {
+            for y in -2..4 {
+                let root = IVec3::new(x, y, z);
+                let (_, overflow) = generator.generate_with_overflow(root);
+                for write in overflow {
+                    let (target, local) = world_to_chunk(write.pos);
+                    let terrain = generator.generate(target);
+                    if terrain.data.get(local.x, local.y, local.z) == BlockId::Air {
+                        return (root, write);
+                    }
+                }
+            }
+        }
+    }
+    panic!("no feature crosses a chunk border");
+}
+
+#[test]
+fn placement_is_deterministic() {
+    let generator = generator();
+    for pos in [IVec3::new(0, 0, 0), IVec3::new(3, 1, -2)] {
+        let (a, overflow_a) = generator.generate_with_overflow(pos);
+        let (b, overflow_b) = generator.generate_with_overflow(pos);
+        assert_eq!(chunk_to_bytes(&a), chunk_to_bytes(&b));
+        assert_eq!(overflow_a, overflow_b);
+    }
+}
+
+#[test]
+fn border_features_match_in_either_order() {
+    let generator = generator();
+    let (root, write) = straddling(generator.as_ref());
+    let (target, _) = world_to_chunk(write.pos);
+
+    let mut worlds = Vec::new();
+    for order in [[root, target], [target, root]] {
+        let mut world = VoxelWorld::new();
+        for pos in order {
+            generate(&mut world, generator.as_ref(), pos);
+        }
+        assert_ne!(world.get_voxel(write.pos), Some(BlockId::Air));
+        assert!(world.unsaved.contains(&target));
+        assert!(!world.feature_writes.contains_key(&target));
+        worlds.push(world);
+    }
+    for pos in [root, target] {
+        let a = worlds[0].get_chunk(pos).unwrap();
+        let b = worlds[1].get_chunk(pos).unwrap();
+        assert_eq!(chunk_to_bytes(&a), chunk_to_bytes(&b));
+    }
+}
+
+#[test]
+fn regenerated_roots_write_once() {
+    let generator = generator();
+    let (root, write) = straddling(generator.as_ref());
+    let (target, _) = world_to_chunk(write.pos);
+
+    let mut world = VoxelWorld::new();
+    generate(&mut world, generator.as_ref(), root);
+    generate(&mut world, generator.as_ref(), target);
+    // Edited chunks don't grow features back
+    world.set_voxel(write.pos, BlockId::Air);
+    world.remove_chunk(root);
+    generate(&mut world, generator.as_ref(), root);
+    assert_eq!(world.get_voxel(write.pos), Some(BlockId::Air));
+
+    // Unloaded roots are forgotten, the writes are queued again for a target
+    // that gets regenerated
+    world.remove_chunk(root);
+    world.remove_chunk(target);
+    assert!(world.features_placed.is_empty());
+    generate(&mut world, generator.as_ref(), root);
+    assert!(world.feature_writes[&target].contains(&write));
+
+    // Writes for far away chunks are taken out to be saved
+    let taken = world.take_feature_writes(|pos| pos != target);
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].0, target);
+    assert!(!world.feature_writes.contains_key(&target));
+}
+
+#[test]
+fn saved_chunks_keep_their_blocks() {
+    let generator = generator();
+    let (root, write) = straddling(generator.as_ref());
+    let (target, _) = world_to_chunk(write.pos);
+
+    // As if loaded from storage, saved before the root was ever generated
+    let mut world = VoxelWorld::new();
+    world.add_chunk(target, generator.generate(target));
+    generate(&mut world, generator.as_ref(), root);
+    assert_eq!(world.get_voxel(write.pos), Some(BlockId::Air));
+    assert!(!world.unsaved.contains(&target));
+}
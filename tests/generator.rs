@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::generator::{GeneratorConfig, GeneratorKind, HeightmapGenerator, WorldGenerator};
+use bevy_cubes::region::chunk_to_bytes;
+
+const KINDS: [GeneratorKind; 4] = [
+    GeneratorKind::Noise,
+    GeneratorKind::Heightmap,
+    GeneratorKind::Biomes,
+    GeneratorKind::Caves,
+];
+
+// Chunk heights spanning every generator's surface range
+const HEIGHTS: std::ops::Range<i32> = -2..5;
+
+// A column of chunks through the surface, serialised for comparison
+fn column(config: &GeneratorConfig) -> Vec<u8> {
+    let generator = config.build();
+    let mut bytes = Vec::new();
+    for y in HEIGHTS {
+        let pos = IVec3::new(1, y, -3);
+        let (chunk, _) = generator.generate_with_overflow(pos);
+        assert_eq!(chunk.position, pos);
+        bytes.extend(chunk_to_bytes(&chunk));
+    }
+    bytes
+}
+
+#[test]
+fn same_seed_same_world() {
+    for kind in KINDS {
+        let config = GeneratorConfig {
+            kind,
+            seed: 1234,
+            features: false,
+            ..default()
+        };
+        assert_eq!(column(&config), column(&config.clone()), "{:?}", kind);
+
+        let reseeded = GeneratorConfig {
+            seed: 4321,
+            ..config.clone()
+        };
+        assert_ne!(column(&config), column(&reseeded), "{:?}", kind);
+    }
+}
+
+#[test]
+fn every_kind_builds() {
+    for kind in KINDS {
+        for features in [false, true] {
+            let config = GeneratorConfig {
+                kind,
+                features,
+                ..default()
+            };
+            let generator = config.build();
+            let mut solid = 0;
+            let mut air = 0;
+            for y in HEIGHTS {
+                let chunk = generator.generate(IVec3::new(0, y, 0));
+                for x in 0..32 {
+                    for y in 0..32 {
+                        for z in 0..32 {
+                            match chunk.data.get(x, y, z) {
+                                BlockId::Air => air += 1,
+                                _ => solid += 1,
+                            }
+                        }
+                    }
+                }
+            }
+            // Every generator has both ground and sky somewhere in the column
+            assert!(solid > 0, "{:?} has no ground", kind);
+            assert!(air > 0, "{:?} has no air", kind);
+        }
+    }
+}
+
+#[test]
+fn heightmap_fills_below_sea_level() {
+    let config = GeneratorConfig {
+        kind: GeneratorKind::Heightmap,
+        sea_level: 80,
+        ..default()
+    };
+    let generator = HeightmapGenerator::new(config.clone());
+    let mut water = 0;
+    for y in HEIGHTS {
+        let pos = IVec3::new(2, y, 1);
+        let chunk = generator.generate(pos);
+        for x in 0..32 {
+            for z in 0..32 {
+                let height = generator.height(pos.x * 32 + x, pos.z * 32 + z);
+                for y in 0..32 {
+                    let world_y = pos.y * 32 + y;
+                    let block = chunk.data.get(x, y, z);
+                    if height - world_y as f32 > 0. {
+                        assert!(block.is_solid());
+                    } else if world_y < config.sea_level {
+                        assert_eq!(block, BlockId::Water);
+                        water += 1;
+                    } else {
+                        assert_eq!(block, BlockId::Air);
+                    }
+                }
+            }
+        }
+    }
+    assert!(water > 0);
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::Chunk;
+use bevy_cubes::light::{light_chunk, LightChannel, MAX_LIGHT};
+use bevy_cubes::world::VoxelWorld;
+
+// One chunk with a stone roof at y = 20, dark underneath
+fn roofed_world() -> VoxelWorld {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for z in 0..32 {
+            chunk.data.set(x, 20, z, BlockId::Stone);
+        }
+    }
+    light_chunk(&mut chunk);
+    let mut world = VoxelWorld::new();
+    world.add_chunk(IVec3::ZERO, chunk);
+    world.stitch_light(IVec3::ZERO);
+    world
+}
+
+fn sky(world: &VoxelWorld, pos: IVec3) -> u8 {
+    world.get_light(LightChannel::Sky, pos).unwrap()
+}
+
+fn block(world: &VoxelWorld, pos: IVec3) -> u8 {
+    world.get_light(LightChannel::Block, pos).unwrap()
+}
+
+#[test]
+fn sky_light_stops_at_roof() {
+    let world = roofed_world();
+    assert_eq!(sky(&world, IVec3::new(5, 25, 5)), MAX_LIGHT);
+    assert_eq!(sky(&world, IVec3::new(5, 20, 5)), 0);
+    assert_eq!(sky(&world, IVec3::new(5, 5, 5)), 0);
+}
+
+#[test]
+fn lamp_lights_and_unlights() {
+    let mut world = roofed_world();
+    let lamp = IVec3::new(10, 5, 10);
+    world.set_voxel(lamp, BlockId::Lamp);
+    assert_eq!(block(&world, lamp), 15);
+    assert_eq!(block(&world, lamp + IVec3::X), 14);
+    assert_eq!(block(&world, lamp + IVec3::new(3, 2, 0)), 10);
+
+    world.set_voxel(lamp, BlockId::Air);
+    assert_eq!(block(&world, lamp), 0);
+    assert_eq!(block(&world, lamp + IVec3::X), 0);
+    assert_eq!(block(&world, lamp + IVec3::new(3, 2, 0)), 0);
+}
+
+#[test]
+fn hole_in_roof_lets_sky_in() {
+    let mut world = roofed_world();
+    world.set_voxel(IVec3::new(5, 20, 5), BlockId::Air);
+    assert_eq!(sky(&world, IVec3::new(5, 5, 5)), MAX_LIGHT);
+    assert_eq!(sky(&world, IVec3::new(6, 5, 5)), MAX_LIGHT - 1);
+
+    world.set_voxel(IVec3::new(5, 20, 5), BlockId::Stone);
+    assert_eq!(sky(&world, IVec3::new(5, 5, 5)), 0);
+    assert_eq!(sky(&world, IVec3::new(6, 5, 5)), 0);
+}
+
+#[test]
+fn light_crosses_chunk_borders() {
+    let mut world = roofed_world();
+    let mut dark = Chunk::new(IVec3::X);
+    for x in 0..32 {
+        for z in 0..32 {
+            dark.data.set(x, 20, z, BlockId::Stone);
+        }
+    }
+    light_chunk(&mut dark);
+    world.add_chunk(IVec3::X, dark);
+    world.stitch_light(IVec3::X);
+
+    world.set_voxel(IVec3::new(30, 5, 5), BlockId::Lamp);
+    assert_eq!(block(&world, IVec3::new(33, 5, 5)), 12);
+}
@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{MeshVertexAttribute, VertexAttributeValues};
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::*;
+use bevy_cubes::lod::{downsample, mesh_lod, MAX_LOD};
+use bevy_cubes::mesher::ChunkMeshes;
+use bevy_cubes::quad::Direction;
+use bevy_cubes::streaming::{chunk_lod, StreamingSettings};
+use bevy_cubes::world::VoxelWorld;
+
+#[test]
+fn cells_take_the_most_common_block() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for y in 0..4 {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, BlockId::Stone);
+            }
+        }
+    }
+    // 3 of 8 voxels in the first 2x2x2 cell
+    chunk.data.set(0, 0, 0, BlockId::Dirt);
+    chunk.data.set(1, 0, 0, BlockId::Dirt);
+    chunk.data.set(0, 1, 0, BlockId::Dirt);
+
+    let lod = downsample(&chunk, 1);
+    assert_eq!(lod.size, 16);
+    assert_eq!(lod.get(IVec3::ZERO), Some(BlockId::Stone));
+    assert_eq!(lod.get(IVec3::new(5, 1, 5)), Some(BlockId::Stone));
+    assert_eq!(lod.get(IVec3::new(5, 2, 5)), Some(BlockId::Air));
+    assert_eq!(lod.get(IVec3::new(16, 0, 0)), None);
+
+    // Exactly half filled cells stay solid, 4 of 8 voxels high
+    let lod = downsample(&chunk, 3);
+    assert_eq!(lod.size, 4);
+    assert_eq!(lod.get(IVec3::ZERO), Some(BlockId::Stone));
+    assert_eq!(lod.get(IVec3::new(0, 1, 0)), Some(BlockId::Air));
+}
+
+#[test]
+fn thin_features_disappear() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for y in 0..32 {
+        chunk.data.set(7, y, 7, BlockId::Log);
+    }
+    let lod = downsample(&chunk, 1);
+    assert!(lod.blocks.iter().all(|block| *block == BlockId::Air));
+}
+
+fn slab(height: i32) -> Chunk {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for y in 0..height {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, BlockId::Stone);
+            }
+        }
+    }
+    chunk
+}
+
+// No neighbours loaded
+fn mesh_alone(chunk: &Chunk, level: u32) -> ChunkMeshes {
+    let neighbours = ChunkNeighbours::new(&VoxelWorld::new(), chunk.position);
+    mesh_lod(chunk, &neighbours, level, false)
+}
+
+fn attribute(mesh: &Mesh, id: MeshVertexAttribute) -> Vec<Vec3> {
+    match mesh.attribute(id) {
+        Some(VertexAttributeValues::Float32x3(values)) => values
+            .iter()
+            .map(|value| Vec3::from_array(*value))
+            .collect(),
+        _ => panic!("missing attribute"),
+    }
+}
+
+#[test]
+fn one_quad_per_cell_face() {
+    // A solid chunk only shows its six sides, each split into cells
+    let full = slab(32);
+    for level in 1..=MAX_LOD {
+        let cells = 32 >> level;
+        let meshes = mesh_alone(&full, level);
+        assert_eq!(meshes.opaque.count_vertices(), 6 * cells * cells * 4);
+        assert!(meshes.transparent.is_none());
+    }
+
+    // Positions stay on the cell grid inside the chunk
+    let meshes = mesh_alone(&slab(16), 2);
+    for pos in attribute(&meshes.opaque, Mesh::ATTRIBUTE_POSITION) {
+        assert_eq!(pos % 4., Vec3::ZERO);
+        assert!(pos.min_element() >= 0. && pos.max_element() <= 32.);
+        assert!(pos.y <= 16.);
+    }
+}
+
+#[test]
+fn border_faces_are_skirts() {
+    // Half height slab: top, bottom and four sides of 8x4 cells
+    let meshes = mesh_alone(&slab(16), 2);
+    let positions = attribute(&meshes.opaque, Mesh::ATTRIBUTE_POSITION);
+    let normals = attribute(&meshes.opaque, Mesh::ATTRIBUTE_NORMAL);
+    assert_eq!(positions.len(), (2 * 64 + 4 * 32) * 4);
+
+    // Faces on the chunk border are kept without neighbours
+    for dir in Direction::ALL {
+        let faces: Vec<Vec3> = positions
+            .iter()
+            .zip(&normals)
+            .filter(|(_, normal)| **normal == dir.normal())
+            .map(|(pos, _)| *pos)
+            .collect();
+        let expected = if dir.offset().y == 0 { 32 } else { 64 };
+        assert_eq!(faces.len(), expected * 4, "{:?}", dir);
+        if dir != Direction::Up {
+            let border = if dir.offset().max_element() > 0 {
+                32.
+            } else {
+                0.
+            };
+            let axis = (0..3).find(|axis| dir.offset()[*axis] != 0).unwrap();
+            assert!(faces.iter().all(|pos| pos[axis] == border), "{:?}", dir);
+        }
+    }
+}
+
+fn filled(pos: IVec3, block: BlockId) -> Chunk {
+    let mut chunk = Chunk::new(pos);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, block);
+            }
+        }
+    }
+    chunk
+}
+
+fn normals(mesh: &Option<Mesh>) -> Vec<Vec3> {
+    mesh.as_ref()
+        .map(|mesh| attribute(mesh, Mesh::ATTRIBUTE_NORMAL))
+        .unwrap_or_default()
+}
+
+#[test]
+fn border_faces_cull_against_neighbours() {
+    // Stone under open water, the stone reaching into the next chunk over
+    let mut world = VoxelWorld::new();
+    let mut water = filled(IVec3::ZERO, BlockId::Water);
+    for x in 0..32 {
+        for z in 0..32 {
+            water.data.set(x, 0, z, BlockId::Stone);
+            water.data.set(x, 1, z, BlockId::Stone);
+        }
+    }
+    let mut next = water.clone();
+    next.position = IVec3::X;
+    world.add_chunk(IVec3::ZERO, water);
+    world.add_chunk(IVec3::X, next);
+    world.add_chunk(IVec3::Y, Chunk::new(IVec3::Y));
+    world.add_chunk(IVec3::NEG_Y, filled(IVec3::NEG_Y, BlockId::Stone));
+
+    let neighbours = ChunkNeighbours::new(&world, IVec3::ZERO);
+    let meshes = mesh_lod(&world.chunks[&IVec3::ZERO], &neighbours, 1, false);
+    // Water only meets air above, no sheets against the other water chunk or
+    // the unloaded ones
+    let water = normals(&meshes.transparent);
+    assert_eq!(water.len(), 16 * 16 * 4);
+    assert!(water.iter().all(|normal| *normal == Vec3::Y));
+
+    // The stone floor is a single cell high at level 1 and shows through
+    // the water. Its side towards the loaded chunk is culled, towards the
+    // unloaded ones it's a skirt.
+    let stone = normals(&Some(meshes.opaque));
+    let count = |dir: Vec3| stone.iter().filter(|normal| **normal == dir).count();
+    assert_eq!(count(Vec3::Y), 16 * 16 * 4);
+    assert_eq!(count(Vec3::X), 0);
+    assert_eq!(count(Vec3::NEG_Y), 0);
+    for side in [Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+        assert_eq!(count(side), 16 * 4);
+    }
+}
+
+#[test]
+fn lod_grows_with_distance() {
+    let settings = StreamingSettings::default();
+    // Every level is used inside the render distance
+    assert!(settings
+        .lod_distances
+        .windows(2)
+        .all(|pair| pair[0] < pair[1]));
+    assert!(settings.lod_distances[MAX_LOD as usize - 1] <= settings.render_distance);
+
+    let center = IVec3::new(10, 2, -4);
+    let lod = |offset: IVec3| chunk_lod(&settings, center, center + offset);
+    assert_eq!(lod(IVec3::ZERO), 0);
+    // Only the horizontal distance counts
+    assert_eq!(lod(IVec3::new(0, 3, 0)), 0);
+    for (i, distance) in settings.lod_distances.iter().enumerate() {
+        assert_eq!(lod(IVec3::new(*distance - 1, 0, 0)), i as u32);
+        assert_eq!(lod(IVec3::new(0, 0, -*distance)), i as u32 + 1);
+    }
+    assert_eq!(lod(IVec3::splat(settings.render_distance)), MAX_LOD);
+}
@@ -0,0 +1,332 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::*;
+use bevy_cubes::light::light_chunk;
+use bevy_cubes::mesher::{mesh_transparent, texture_tile, MeshingMode};
+use bevy_cubes::quad::Direction;
+use bevy_cubes::world::VoxelWorld;
+
+fn quads(chunk: &Chunk, mode: MeshingMode) -> usize {
+    chunk.build_mesh(&VoxelWorld::new(), mode).quad_count()
+}
+
+#[test]
+fn single_cube() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    chunk.data.set(5, 5, 5, BlockId::Stone);
+
+    assert_eq!(quads(&chunk, MeshingMode::Naive), 6);
+    assert_eq!(quads(&chunk, MeshingMode::Greedy), 6);
+}
+
+#[test]
+fn flat_slab() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for z in 0..32 {
+            chunk.data.set(x, 5, z, BlockId::Grass);
+        }
+    }
+
+    assert_eq!(quads(&chunk, MeshingMode::Naive), 32 * 32 * 2 + 32 * 4);
+    assert_eq!(quads(&chunk, MeshingMode::Greedy), 6);
+}
+
+#[test]
+fn different_blocks_are_not_merged() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    chunk.data.set(5, 5, 5, BlockId::Stone);
+    chunk.data.set(6, 5, 5, BlockId::Stone);
+    assert_eq!(quads(&chunk, MeshingMode::Greedy), 6);
+
+    chunk.data.set(6, 5, 5, BlockId::Dirt);
+    assert_eq!(quads(&chunk, MeshingMode::Greedy), 10);
+}
+
+#[test]
+fn full_chunk_without_floor() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, BlockId::Stone);
+            }
+        }
+    }
+
+    // The bottom face is skipped when there is no chunk below
+    assert_eq!(quads(&chunk, MeshingMode::Greedy), 5);
+}
+
+fn sorted_quads(chunk: &Chunk, world: &VoxelWorld, mode: MeshingMode) -> Vec<Vec<u32>> {
+    let buffers = chunk.build_mesh(world, mode);
+    let mut quads: Vec<Vec<u32>> = buffers
+        .positions
+        .chunks(4)
+        .zip(buffers.colors.chunks(4))
+        .map(|(positions, colors)| {
+            positions
+                .iter()
+                .flatten()
+                .chain(colors.iter().flatten())
+                .map(|f| f.to_bits())
+                .collect()
+        })
+        .collect();
+    quads.sort();
+    quads
+}
+
+#[test]
+fn binary_matches_naive() {
+    let mut world = VoxelWorld::new();
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                let pos = IVec3::new(x, y, z);
+                world.add_chunk(pos, gen_chunk_flat(pos));
+            }
+        }
+    }
+    for chunk in world.chunks.values() {
+        assert_eq!(
+            sorted_quads(chunk, &world, MeshingMode::Naive),
+            sorted_quads(chunk, &world, MeshingMode::Binary),
+        );
+    }
+
+    let mut chunk = gen_chunk(IVec3::new(0, 1, 0));
+    chunk.data.set(0, 0, 0, BlockId::Sand);
+    let world = VoxelWorld::new();
+    assert_eq!(
+        sorted_quads(&chunk, &world, MeshingMode::Naive),
+        sorted_quads(&chunk, &world, MeshingMode::Binary),
+    );
+}
+
+#[test]
+fn ao_darkens_corners_next_to_blocks() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for z in 0..32 {
+            chunk.data.set(x, 5, z, BlockId::Stone);
+        }
+    }
+    chunk.data.set(5, 6, 5, BlockId::Stone);
+    let buffers = chunk.build_mesh(&VoxelWorld::new(), MeshingMode::Naive);
+
+    // Top face of the floor voxel next to the block
+    let quad = buffers
+        .positions
+        .chunks(4)
+        .position(|quad| {
+            quad.iter()
+                .all(|p| p[1] == 6. && (4. ..=5.).contains(&p[0]) && (5. ..=6.).contains(&p[2]))
+        })
+        .unwrap();
+    for i in quad * 4..quad * 4 + 4 {
+        let lit = buffers.colors[i][0] == 1.;
+        assert_eq!(lit, buffers.positions[i][0] == 4.);
+    }
+}
+
+#[test]
+fn greedy_ao_matches_naive() {
+    let mut world = VoxelWorld::new();
+    for x in 0..2 {
+        for z in 0..2 {
+            let pos = IVec3::new(x, 0, z);
+            world.add_chunk(pos, gen_chunk_flat(pos));
+        }
+    }
+    for chunk in world.chunks.values() {
+        let naive = chunk.build_mesh(&world, MeshingMode::Naive);
+        let vertex = |positions: &[f32; 3], normal: Vec3| {
+            (positions.map(f32::to_bits), normal.to_array().map(f32::to_bits))
+        };
+        let colors: std::collections::HashMap<_, _> = naive
+            .positions
+            .iter()
+            .zip(&naive.normals)
+            .map(|(p, n)| vertex(p, *n))
+            .zip(naive.colors.iter().copied())
+            .collect();
+
+        let greedy = chunk.build_mesh(&world, MeshingMode::Greedy);
+        assert!(greedy.quad_count() < naive.quad_count());
+        for i in 0..greedy.positions.len() {
+            let key = vertex(&greedy.positions[i], greedy.normals[i]);
+            assert_eq!(colors[&key], greedy.colors[i]);
+        }
+    }
+}
+
+#[test]
+fn corner_edits_dirty_diagonal_chunks() {
+    // Solid stone so edits don't change any light
+    let mut stone = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                stone.data.set(x, y, z, BlockId::Stone);
+            }
+        }
+    }
+    light_chunk(&mut stone);
+    let mut world = VoxelWorld::new();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let pos = IVec3::new(x, y, z);
+                world.add_chunk(
+                    pos,
+                    Chunk {
+                        position: pos,
+                        ..stone.clone()
+                    },
+                );
+            }
+        }
+    }
+    let expected = |positions: &[[i32; 3]]| {
+        let mut positions: Vec<IVec3> = positions.iter().map(|p| IVec3::from_array(*p)).collect();
+        positions.sort_by_key(|pos| pos.to_array());
+        positions
+    };
+    let dirty = |world: &mut VoxelWorld, voxel: IVec3| {
+        world.dirty.clear();
+        world.set_voxel(voxel, BlockId::Dirt);
+        let mut positions: Vec<IVec3> = world.dirty.iter().copied().collect();
+        positions.sort_by_key(|pos| pos.to_array());
+        positions
+    };
+
+    // Corner of chunk 0 touches all eight chunks around it
+    let corner = dirty(&mut world, IVec3::new(0, 0, 31));
+    assert_eq!(
+        corner,
+        expected(&[
+            [-1, -1, 0],
+            [-1, -1, 1],
+            [-1, 0, 0],
+            [-1, 0, 1],
+            [0, -1, 0],
+            [0, -1, 1],
+            [0, 0, 0],
+            [0, 0, 1],
+        ])
+    );
+    // Edges touch four, faces two
+    let edge = dirty(&mut world, IVec3::new(31, 10, 0));
+    assert_eq!(
+        edge,
+        expected(&[[0, 0, -1], [0, 0, 0], [1, 0, -1], [1, 0, 0]])
+    );
+    let face = dirty(&mut world, IVec3::new(10, 31, 10));
+    assert_eq!(face, expected(&[[0, 0, 0], [0, 1, 0]]));
+
+    // Adding a chunk dirties all 26 neighbours
+    world.dirty.clear();
+    world.add_chunk(IVec3::ZERO, stone);
+    assert_eq!(world.dirty.len(), 27);
+}
+
+#[test]
+fn greedy_uvs_repeat_per_voxel() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for z in 0..8 {
+            chunk.data.set(x, 5, z, BlockId::Grass);
+        }
+    }
+    let buffers = chunk.build_mesh(&VoxelWorld::new(), MeshingMode::Greedy);
+    let top = buffers
+        .normals
+        .iter()
+        .position(|normal| *normal == Vec3::Y)
+        .unwrap();
+
+    let uvs = &buffers.uvs[top..top + 4];
+    assert!(uvs.contains(&[0., 0.]));
+    assert!(uvs.contains(&[32., 8.]));
+    assert!(buffers.tiles[top..top + 4]
+        .iter()
+        .all(|tile| *tile == texture_tile(BlockId::Grass, Direction::Up)));
+}
+
+#[test]
+fn vertices_are_chunk_local() {
+    let pos = IVec3::new(100_000, 2, -100_000);
+    let mut chunk = Chunk::new(pos);
+    chunk.data.set(0, 0, 0, BlockId::Stone);
+    chunk.data.set(31, 31, 31, BlockId::Stone);
+    for mode in [MeshingMode::Naive, MeshingMode::Greedy, MeshingMode::Binary] {
+        let buffers = chunk.build_mesh(&VoxelWorld::new(), mode);
+        // No bottom face on the voxel at y 0, there is no chunk below
+        assert_eq!(buffers.quad_count(), 11);
+        assert!(buffers
+            .positions
+            .iter()
+            .flatten()
+            .all(|p| (0. ..=32.).contains(p)));
+    }
+}
+
+fn filled(pos: IVec3, block: BlockId) -> Chunk {
+    let mut chunk = Chunk::new(pos);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, block);
+            }
+        }
+    }
+    chunk
+}
+
+#[test]
+fn water_is_culled_against_water() {
+    // A 3x3x3 pool is one quad per side
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    for x in 4..7 {
+        for y in 4..7 {
+            for z in 4..7 {
+                chunk.data.set(x, y, z, BlockId::Water);
+            }
+        }
+    }
+    let neighbours = ChunkNeighbours::new(&VoxelWorld::new(), IVec3::ZERO);
+    assert_eq!(mesh_transparent(&chunk, &neighbours, false).quad_count(), 6);
+
+    // No sheet between two water chunks
+    let mut world = VoxelWorld::new();
+    for pos in [IVec3::ZERO, IVec3::X] {
+        world.add_chunk(pos, filled(pos, BlockId::Water));
+    }
+    let neighbours = ChunkNeighbours::new(&world, IVec3::ZERO);
+    let buffers = mesh_transparent(&world.chunks[&IVec3::ZERO], &neighbours, false);
+    // Up and the three unloaded sides, nothing below the world floor
+    assert_eq!(buffers.quad_count(), 4);
+    assert!(!buffers.normals.contains(&Vec3::X));
+}
+
+#[test]
+fn water_faces_against_solid_blocks() {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    chunk.data.set(5, 5, 5, BlockId::Water);
+    chunk.data.set(6, 5, 5, BlockId::Stone);
+    let world = VoxelWorld::new();
+    let neighbours = ChunkNeighbours::new(&world, IVec3::ZERO);
+
+    // The water face behind the stone is hidden
+    let water = mesh_transparent(&chunk, &neighbours, false);
+    assert_eq!(water.quad_count(), 5);
+    assert!(!water.normals.contains(&Vec3::X));
+
+    // The stone keeps its face looking into the water
+    for mode in [MeshingMode::Naive, MeshingMode::Greedy, MeshingMode::Binary] {
+        let stone = chunk.build_mesh(&world, mode);
+        assert_eq!(stone.quad_count(), 6);
+        assert!(stone.normals.contains(&Vec3::NEG_X));
+    }
+}
@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::Chunk;
+use bevy_cubes::octree::ChunkOctree;
+use bevy_cubes::world::VoxelWorld;
+
+fn filled(pos: IVec3, block: BlockId) -> Chunk {
+    let mut chunk = Chunk::new(pos);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                chunk.data.set(x, y, z, block);
+            }
+        }
+    }
+    chunk.data.compact();
+    chunk
+}
+
+#[test]
+fn uniform_nodes_collapse() {
+    let mut world = VoxelWorld::new();
+    for x in 0..4 {
+        for y in -4..0 {
+            for z in 0..4 {
+                let pos = IVec3::new(x, y, z);
+                world.add_chunk(pos, filled(pos, BlockId::Stone));
+            }
+        }
+    }
+    // One root with a branch down to the collapsed 4x4x4 node
+    assert_eq!(world.octree.node_count(), 4);
+    let summary = world.octree.summary(IVec3::new(1, -1, 1), 2).unwrap();
+    assert_eq!(summary.chunks, 64);
+    assert_eq!(summary.uniform, Some(BlockId::Stone));
+    assert_eq!(summary.solid_voxels, 64 * 32 * 32 * 32);
+
+    // Editing a chunk splits the node back up
+    world.set_voxel(IVec3::new(40, -10, 40), BlockId::Air);
+    let summary = world.octree.summary(IVec3::new(1, -1, 1), 2).unwrap();
+    assert_eq!(summary.uniform, None);
+    assert_eq!(summary.solid_voxels, 64 * 32 * 32 * 32 - 1);
+    assert_eq!(
+        world
+            .chunks_in(IVec3::new(0, -4, 0), IVec3::new(3, -1, 3))
+            .len(),
+        64
+    );
+    assert!(world.get_chunk(IVec3::new(1, -1, 1)).is_some());
+}
+
+#[test]
+fn range_queries() {
+    let mut octree = ChunkOctree::default();
+    let positions = [
+        IVec3::new(0, 0, 0),
+        IVec3::new(5, 1, -3),
+        IVec3::new(-40, 0, 7),
+        IVec3::new(100, -2, 100),
+    ];
+    for pos in positions {
+        octree.insert(pos, Default::default());
+    }
+
+    let mut found = octree.chunks_in(IVec3::splat(-50), IVec3::splat(10));
+    found.sort_by_key(|pos| pos.to_array());
+    let mut expected = positions[..3].to_vec();
+    expected.sort_by_key(|pos| pos.to_array());
+    assert_eq!(found, expected);
+
+    octree.remove(IVec3::new(5, 1, -3));
+    assert_eq!(
+        octree.chunks_in(IVec3::splat(-50), IVec3::splat(10)).len(),
+        2
+    );
+    assert!(octree.summary(IVec3::new(5, 1, -3), 0).is_none());
+    assert_eq!(
+        octree
+            .chunks_in(IVec3::new(90, -10, 90), IVec3::splat(110))
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::*;
+use bevy_cubes::light::brightness;
+use bevy_cubes::mesher::{texture_tile, MeshingMode, AO_CURVE, ATLAS_COLUMNS};
+use bevy_cubes::packed::PackedVertex;
+use bevy_cubes::quad::Direction;
+use bevy_cubes::world::VoxelWorld;
+
+const SHADER: &str = include_str!("../assets/shaders/voxel.wgsl");
+
+#[test]
+fn pack_round_trip() {
+    for face in Direction::ALL {
+        let vertex = PackedVertex {
+            pos: UVec3::new(32, 0, 17),
+            face,
+            ao: 2,
+            light: 15,
+            texture: 31,
+        };
+        assert_eq!(PackedVertex::unpack(vertex.pack()), Some(vertex));
+    }
+    let zero = PackedVertex {
+        pos: UVec3::ZERO,
+        face: Direction::North,
+        ao: 0,
+        light: 0,
+        texture: 0,
+    };
+    assert_eq!(zero.pack(), 0);
+    let max = PackedVertex {
+        pos: UVec3::splat(63),
+        face: Direction::Down,
+        ao: 3,
+        light: 15,
+        texture: 31,
+    };
+    assert_eq!(PackedVertex::unpack(max.pack()), Some(max));
+}
+
+#[test]
+fn invalid_faces_dont_unpack() {
+    for face in [6, 7] {
+        assert_eq!(PackedVertex::unpack(face << 18), None);
+    }
+    assert_eq!(PackedVertex::unpack(u32::MAX), None);
+    // Every other bit set with the last valid face
+    let down = PackedVertex::unpack(!(0b111 << 18) | (5 << 18)).unwrap();
+    assert_eq!(down.face, Direction::Down);
+}
+
+#[test]
+fn packed_matches_full_vertices() {
+    let mut world = VoxelWorld::new();
+    for x in 0..2 {
+        for z in 0..2 {
+            let pos = IVec3::new(x, 0, z);
+            world.add_chunk(pos, gen_chunk_flat(pos));
+        }
+    }
+    let mut chunk = Chunk::new(IVec3::new(5, 0, 5));
+    chunk.data.set(3, 4, 5, BlockId::Lamp);
+    chunk.data.set(3, 5, 5, BlockId::Log);
+
+    let mut meshes = vec![(chunk, MeshingMode::Naive)];
+    for chunk in world.chunks.values() {
+        meshes.push((Chunk::clone(chunk), MeshingMode::Greedy));
+    }
+    for (chunk, mode) in meshes {
+        let neighbours = ChunkNeighbours::new(&world, chunk.position);
+        let full = chunk.build_mesh_with_neighbours(&neighbours, mode, false);
+        let packed = chunk.build_mesh_with_neighbours(&neighbours, mode, true);
+        // Only one representation is filled
+        assert!(full.packed.is_empty());
+        assert!(packed.positions.is_empty() && packed.colors.is_empty());
+        assert_eq!(packed.indices, full.indices);
+        assert_eq!(packed.packed.len(), full.positions.len());
+
+        for (i, packed) in packed.packed.iter().enumerate() {
+            let vertex = PackedVertex::unpack(*packed).unwrap();
+            assert_eq!(vertex.pos.as_vec3().to_array(), full.positions[i]);
+            assert_eq!(vertex.face.normal(), full.normals[i]);
+            let shade = brightness(vertex.light) * AO_CURVE[vertex.ao as usize];
+            assert_eq!(full.colors[i], [shade, shade, shade, 1.]);
+            let column = full.tiles[i] % ATLAS_COLUMNS;
+            assert_eq!(
+                vertex.texture as u32 * ATLAS_COLUMNS + column,
+                full.tiles[i]
+            );
+        }
+    }
+}
+
+// Atlas column the shader picks for a face index, read from the if chain in
+// the packed vertex shader
+fn shader_column(face: usize) -> u32 {
+    let start = SHADER.find("var column = 1u;").unwrap();
+    let end = start + SHADER[start..].find("out.tile").unwrap();
+    let branch = format!("face == {}u {{", face);
+    match SHADER[start..end].find(&branch) {
+        Some(at) => {
+            let rest = &SHADER[start + at..end];
+            let value = rest.split("column = ").nth(1).unwrap();
+            value[..value.find('u').unwrap()].parse().unwrap()
+        }
+        None => 1,
+    }
+}
+
+#[test]
+fn shader_matches_texture_tile() {
+    for dir in Direction::ALL {
+        for block in [BlockId::Grass, BlockId::Log, BlockId::Water] {
+            let texture = block.texture() as u32;
+            assert_eq!(
+                texture * ATLAS_COLUMNS + shader_column(dir.index()),
+                texture_tile(block, dir),
+                "{:?} {:?}",
+                dir,
+                block
+            );
+        }
+    }
+    let curve: Vec<String> = AO_CURVE.iter().map(|ao| format!("{:?}", ao)).collect();
+    assert!(SHADER.contains(&format!("array<f32, 4>({})", curve.join(", "))));
+}
@@ -0,0 +1,131 @@
+use bevy_cubes::block::BlockId;
+use bevy_cubes::palette::BlockStorage;
+
+const VOLUME: usize = 32 * 32 * 32;
+
+fn bits(storage: &BlockStorage) -> Option<u32> {
+    match storage {
+        BlockStorage::Single(_) => None,
+        BlockStorage::Paletted { bits, .. } => Some(*bits),
+    }
+}
+
+// Deterministic block pattern using the first `kinds` blocks
+fn pattern(index: usize, kinds: usize) -> BlockId {
+    BlockId::ALL[(index * 7 + index / 5) % kinds]
+}
+
+#[test]
+fn single_block_fast_path() {
+    let mut storage = BlockStorage::default();
+    assert_eq!(storage.single_block(), Some(BlockId::Air));
+    assert_eq!(storage.get(1234), BlockId::Air);
+
+    // Writing the same block keeps it Single
+    storage.set(BlockStorage::index(3, 4, 5), BlockId::Air);
+    assert_eq!(storage.single_block(), Some(BlockId::Air));
+
+    let storage = BlockStorage::from_blocks(std::iter::repeat(BlockId::Stone).take(VOLUME));
+    assert_eq!(storage.single_block(), Some(BlockId::Stone));
+    assert_eq!(storage.get(VOLUME - 1), BlockId::Stone);
+}
+
+#[test]
+fn write_upgrades_to_palette() {
+    let mut storage = BlockStorage::Single(BlockId::Stone);
+    let index = BlockStorage::index(31, 0, 17);
+    storage.set(index, BlockId::Dirt);
+
+    assert_eq!(storage.single_block(), None);
+    assert_eq!(bits(&storage), Some(1));
+    assert_eq!(storage.get(index), BlockId::Dirt);
+    // The old block is kept everywhere else
+    assert_eq!(storage.get(0), BlockId::Stone);
+    assert_eq!(storage.get(index + 1), BlockId::Stone);
+}
+
+#[test]
+fn repacks_across_bit_widths() {
+    // 2 -> 3 entries goes from 1 to 2 bits, 4 -> 5 from 2 to 4 bits
+    for (kinds, expected_bits) in [(2, 1), (3, 2), (4, 2), (5, 4), (BlockId::ALL.len(), 4)] {
+        let expected: Vec<BlockId> = (0..VOLUME).map(|index| pattern(index, kinds)).collect();
+        let mut storage = BlockStorage::default();
+        for (index, block) in expected.iter().enumerate() {
+            storage.set(index, *block);
+        }
+        assert_eq!(bits(&storage), Some(expected_bits), "{} kinds", kinds);
+        for (index, block) in expected.iter().enumerate() {
+            assert_eq!(storage.get(index), *block);
+        }
+
+        // Building from the same blocks gives the same contents
+        let built = BlockStorage::from_blocks(expected.iter().copied());
+        assert_eq!(bits(&built), Some(expected_bits));
+        assert!((0..VOLUME).all(|index| built.get(index) == expected[index]));
+    }
+}
+
+#[test]
+fn compact_drops_unused_entries() {
+    let mut storage = BlockStorage::default();
+    for (index, block) in BlockId::ALL.iter().enumerate() {
+        storage.set(index, *block);
+    }
+    assert_eq!(bits(&storage), Some(4));
+
+    // Back to two blocks in use
+    for index in 2..BlockId::ALL.len() {
+        storage.set(index, BlockId::Air);
+    }
+    storage.compact();
+    assert_eq!(bits(&storage), Some(1));
+    assert_eq!(storage.get(1), BlockId::ALL[1]);
+    assert_eq!(storage.get(5), BlockId::Air);
+
+    storage.set(1, BlockId::Air);
+    assert_eq!(storage.single_block(), None);
+    storage.compact();
+    assert_eq!(storage.single_block(), Some(BlockId::Air));
+}
+
+#[test]
+fn memory_usage_grows_with_bit_width() {
+    let single = BlockStorage::default().memory_usage();
+    let mut storage = BlockStorage::default();
+    storage.set(0, BlockId::Stone);
+    let one_bit = storage.memory_usage();
+    for (index, block) in BlockId::ALL.iter().enumerate() {
+        storage.set(index, *block);
+    }
+    let four_bits = storage.memory_usage();
+
+    assert_eq!(single, std::mem::size_of::<BlockStorage>());
+    assert!(one_bit >= single + VOLUME / 8);
+    assert!(four_bits >= single + VOLUME / 2);
+    assert!(four_bits < one_bit * 5);
+}
+
+#[test]
+fn column_masks_match_get() {
+    for kinds in [1, 2, 3, 5, BlockId::ALL.len()] {
+        let storage = BlockStorage::from_blocks((0..VOLUME).map(|index| pattern(index, kinds)));
+        let [solid, stone] =
+            storage.column_masks([BlockId::is_solid, |block| block == BlockId::Stone]);
+        for x in 0..32 {
+            for y in 0..32 {
+                let first = BlockStorage::index(x, y, 0);
+                assert_eq!(storage.column_mask(first, BlockId::is_solid), solid[x][y]);
+                for z in 0..32 {
+                    let block = storage.get(first + z);
+                    assert_eq!(
+                        solid[x][y] >> z & 1 == 1,
+                        block.is_solid(),
+                        "{} kinds",
+                        kinds
+                    );
+                    assert_eq!(stone[x][y] >> z & 1 == 1, block == BlockId::Stone);
+                }
+            }
+        }
+    }
+}
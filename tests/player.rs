@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::Chunk;
+use bevy_cubes::player::{Collider, PlayerSettings};
+use bevy_cubes::world::VoxelWorld;
+
+// Stone floor up to y = 9 with solid boxes on top, inclusive bounds
+fn world_with(boxes: &[(IVec3, IVec3)]) -> VoxelWorld {
+    let mut chunk = Chunk::new(IVec3::ZERO);
+    let floor = (IVec3::ZERO, IVec3::new(31, 9, 31));
+    for (min, max) in boxes.iter().chain([&floor]) {
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    chunk.data.set(x, y, z, BlockId::Stone);
+                }
+            }
+        }
+    }
+    let mut world = VoxelWorld::new();
+    world.add_chunk(IVec3::ZERO, chunk);
+    world
+}
+
+fn collider() -> Collider {
+    Collider::new(&PlayerSettings::default())
+}
+
+#[test]
+fn lands_on_the_floor() {
+    let world = world_with(&[]);
+    let mut feet = Vec3::new(16., 20.3, 16.);
+    assert!(collider().move_axis(&world, &mut feet, 1, -15.));
+    assert_eq!(feet, Vec3::new(16., 10., 16.));
+
+    // A fall much longer than the collider still stops on the floor
+    let mut feet = Vec3::new(16., 25., 16.);
+    assert!(collider().move_axis(&world, &mut feet, 1, -200.));
+    assert_eq!(feet.y, 10.);
+
+    // Standing on it doesn't collide sideways
+    assert!(!collider().move_axis(&world, &mut feet, 0, 5.));
+    assert_eq!(feet, Vec3::new(21., 10., 16.));
+}
+
+#[test]
+fn stops_at_walls() {
+    let world = world_with(&[(IVec3::new(20, 10, 0), IVec3::new(21, 31, 31))]);
+    let mut feet = Vec3::new(10., 10., 16.);
+    assert!(collider().move_axis(&world, &mut feet, 0, 20.));
+    // Flush with the wall, half the width away from it
+    assert_eq!(feet, Vec3::new(16., 10., 16.));
+
+    // Moving away is free
+    assert!(!collider().move_axis(&world, &mut feet, 0, -3.));
+    assert_eq!(feet.x, 13.);
+}
+
+#[test]
+fn steps_onto_low_ledges() {
+    let settings = PlayerSettings::default();
+    let ledge = |height: i32| (IVec3::new(20, 10, 0), IVec3::new(31, 9 + height, 31));
+
+    let world = world_with(&[ledge(4)]);
+    let mut feet = Vec3::new(10., 10., 16.);
+    assert!(!collider().step_axis(&world, &mut feet, 0, 10., settings.step_height));
+    assert_eq!(feet, Vec3::new(20., 14., 16.));
+
+    // Too high to step onto, stays against it
+    let world = world_with(&[ledge(8)]);
+    let mut feet = Vec3::new(10., 10., 16.);
+    assert!(collider().step_axis(&world, &mut feet, 0, 10., settings.step_height));
+    assert_eq!(feet, Vec3::new(16., 10., 16.));
+
+    // No stepping while in the air
+    let world = world_with(&[ledge(4)]);
+    let mut feet = Vec3::new(10., 10., 16.);
+    assert!(collider().step_axis(&world, &mut feet, 0, 10., 0.));
+    assert_eq!(feet, Vec3::new(16., 10., 16.));
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::Chunk;
+use bevy_cubes::quad::Direction;
+use bevy_cubes::world::VoxelWorld;
+
+fn world_with(blocks: &[(IVec3, BlockId)]) -> VoxelWorld {
+    let mut world = VoxelWorld::new();
+    for pos in [IVec3::ZERO, IVec3::NEG_ONE, IVec3::new(-1, 0, 0)] {
+        world.add_chunk(pos, Chunk::new(pos));
+    }
+    for (pos, block) in blocks {
+        world.set_voxel(*pos, *block);
+    }
+    world
+}
+
+#[test]
+fn hits_face_along_axis() {
+    let world = world_with(&[(IVec3::new(5, 2, 2), BlockId::Stone)]);
+    let hit = world
+        .raycast(Vec3::new(0.5, 2.5, 2.5), Vec3::X, 32.)
+        .unwrap();
+    assert_eq!(hit.pos, IVec3::new(5, 2, 2));
+    assert_eq!(hit.face, Direction::South);
+    assert_eq!(hit.block, BlockId::Stone);
+    assert!((hit.distance - 4.5).abs() < 1e-5);
+
+    assert!(world.raycast(Vec3::new(0.5, 2.5, 2.5), Vec3::X, 4.).is_none());
+    assert!(world.raycast(Vec3::new(0.5, 2.5, 2.5), Vec3::NEG_X, 32.).is_none());
+}
+
+#[test]
+fn hits_negative_coordinates() {
+    let world = world_with(&[(IVec3::new(-3, 4, 1), BlockId::Dirt)]);
+    let hit = world
+        .raycast(Vec3::new(2.5, 10.5, 1.5), Vec3::new(-5., -6., 0.), 32.)
+        .unwrap();
+    assert_eq!(hit.pos, IVec3::new(-3, 4, 1));
+
+    let hit = world
+        .raycast(Vec3::new(-2.5, 10.5, 1.5), Vec3::NEG_Y, 32.)
+        .unwrap();
+    assert_eq!(hit.pos, IVec3::new(-3, 4, 1));
+    assert_eq!(hit.face, Direction::Up);
+    assert!((hit.distance - 5.5).abs() < 1e-5);
+}
+
+#[test]
+fn rejects_unbounded_rays() {
+    let world = world_with(&[]);
+    let origin = Vec3::new(0.5, 2.5, 2.5);
+    assert!(world.raycast(origin, Vec3::X, f32::INFINITY).is_none());
+    assert!(world.raycast(origin, Vec3::X, f32::NAN).is_none());
+    assert!(world.raycast(Vec3::NAN, Vec3::X, 32.).is_none());
+    assert!(world.raycast(origin, Vec3::new(f32::NAN, 0., 0.), 32.).is_none());
+}
@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::*;
+use bevy_cubes::features::FeatureWrite;
+use bevy_cubes::region::*;
+use std::fs;
+
+fn storage(name: &str) -> ChunkStorage {
+    let dir = std::env::temp_dir().join(format!("bevy-cubes-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    ChunkStorage::new(dir)
+}
+
+fn assert_same(a: &Chunk, b: &Chunk) {
+    assert_eq!(a.position, b.position);
+    assert_eq!(chunk_to_bytes(a), chunk_to_bytes(b));
+}
+
+#[test]
+fn compression_round_trip() {
+    let chunk = gen_chunk_flat(IVec3::new(3, 1, -2));
+    let bytes = chunk_to_bytes(&chunk);
+    let compressed = compress(&bytes);
+    assert!(compressed.len() < bytes.len());
+    assert_eq!(decompress(&compressed).unwrap(), bytes);
+
+    let full = vec![BlockId::Stone as u8; 32 * 32 * 32];
+    assert_eq!(decompress(&compress(&full)).unwrap(), full);
+}
+
+#[test]
+fn save_and_load_chunks() {
+    let storage = storage("save-load");
+    // Same region, a different region and negative coordinates
+    let positions = [
+        IVec3::new(0, 0, 0),
+        IVec3::new(15, 0, 3),
+        IVec3::new(16, 0, 0),
+        IVec3::new(-1, -2, -17),
+    ];
+    for pos in positions {
+        storage.save_chunk(&gen_chunk(pos)).unwrap();
+    }
+    for pos in positions {
+        let loaded = storage.load_chunk(pos).unwrap().unwrap();
+        assert_same(&loaded, &gen_chunk(pos));
+    }
+    assert!(storage.load_chunk(IVec3::new(1, 0, 0)).unwrap().is_none());
+    assert!(storage.load_chunk(IVec3::new(0, 5, 0)).unwrap().is_none());
+
+    // Overwriting keeps the other chunks of the region intact
+    let mut edited = gen_chunk(IVec3::ZERO);
+    edited.data.set(1, 2, 3, BlockId::Sand);
+    storage.save_chunk(&edited).unwrap();
+    assert_same(&storage.load_chunk(IVec3::ZERO).unwrap().unwrap(), &edited);
+    let other = IVec3::new(15, 0, 3);
+    assert_same(&storage.load_chunk(other).unwrap().unwrap(), &gen_chunk(other));
+
+    fs::remove_dir_all(storage.dir()).unwrap();
+}
+
+#[test]
+fn save_chunks_in_batches() {
+    let storage = storage("batch");
+    let positions = [
+        IVec3::new(0, 0, 0),
+        IVec3::new(15, 0, 3),
+        IVec3::new(16, 0, 0),
+        IVec3::new(-1, -2, -17),
+    ];
+    let chunks: Vec<Chunk> = positions.iter().map(|pos| gen_chunk(*pos)).collect();
+    storage.save_chunks(&chunks).unwrap();
+    for chunk in &chunks {
+        assert_same(&storage.load_chunk(chunk.position).unwrap().unwrap(), chunk);
+    }
+
+    // A later batch only replaces its own chunks
+    let mut edited = gen_chunk(IVec3::new(15, 0, 3));
+    edited.data.set(4, 5, 6, BlockId::Sand);
+    storage.save_chunks([&edited]).unwrap();
+    let load = |pos| storage.load_chunk(pos).unwrap().unwrap();
+    assert_same(&load(edited.position), &edited);
+    assert_same(&load(IVec3::ZERO), &chunks[0]);
+
+    // Nothing to write leaves the directory alone
+    storage.save_chunks([]).unwrap();
+    fs::remove_dir_all(storage.dir()).unwrap();
+    storage.save_chunks([]).unwrap();
+    assert!(!storage.dir().exists());
+}
+
+#[test]
+fn save_and_load_feature_writes() {
+    let storage = storage("features");
+    let target = IVec3::new(-3, 2, 17);
+    let origin = target * 32;
+    let write = |offset: IVec3, block| FeatureWrite {
+        pos: origin + offset,
+        block,
+    };
+    let first = [
+        write(IVec3::ZERO, BlockId::Log),
+        write(IVec3::new(31, 31, 31), BlockId::Leaves),
+    ];
+    let second = [
+        write(IVec3::new(31, 31, 31), BlockId::Leaves),
+        write(IVec3::new(4, 0, 9), BlockId::Stone),
+    ];
+    assert!(storage.load_feature_writes(target).unwrap().is_empty());
+
+    // Saving again adds to the writes already there, without duplicates
+    storage.save_feature_writes([(target, &first[..])]).unwrap();
+    storage.save_feature_writes([(target, &second[..])]).unwrap();
+    let mut loaded = storage.load_feature_writes(target).unwrap();
+    loaded.sort_by_key(|write| write.pos.to_array());
+    let mut expected = vec![first[0], first[1], second[1]];
+    expected.sort_by_key(|write| write.pos.to_array());
+    assert_eq!(loaded, expected);
+
+    // They don't show up as a saved chunk
+    assert!(storage.load_chunk(target).unwrap().is_none());
+    assert!(storage.load_feature_writes(target + IVec3::Z).unwrap().is_empty());
+
+    fs::remove_dir_all(storage.dir()).unwrap();
+}
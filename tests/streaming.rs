@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_cubes::generator::{ActiveGenerator, GeneratorConfig, GeneratorKind};
+use bevy_cubes::streaming::{ChunkStreamingPlugin, StreamingSettings};
+use bevy_cubes::world::{VoxelWorld, VoxelWorldPlugin};
+use std::collections::HashSet;
+
+fn app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        VoxelWorldPlugin,
+        ChunkStreamingPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .insert_resource(StreamingSettings {
+        render_distance: 2,
+        vertical_distance: 1,
+        chunks_per_frame: 100,
+        results_per_frame: 100,
+        ..default()
+    })
+    // Flat ground keeps generation quick
+    .insert_resource(ActiveGenerator(
+        GeneratorConfig {
+            kind: GeneratorKind::Heightmap,
+            features: false,
+            ..default()
+        }
+        .build(),
+    ));
+    app.world_mut()
+        .spawn((Camera3d::default(), Transform::default()));
+    app
+}
+
+// Updates until the chunks in range have all been generated
+fn settle(app: &mut App) -> HashSet<IVec3> {
+    for _ in 0..500 {
+        app.update();
+        let world = app.world().resource::<VoxelWorld>();
+        if !world.chunks.is_empty() && world.chunks.len() == world.entities.len() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    }
+    let world = app.world().resource::<VoxelWorld>();
+    assert_eq!(world.chunks.len(), world.entities.len());
+    world.chunks.keys().copied().collect()
+}
+
+fn expected(center: IVec3) -> HashSet<IVec3> {
+    let mut expected = HashSet::new();
+    for x in -2..=2 {
+        for y in -1..=1 {
+            for z in -2..=2 {
+                let offset = IVec3::new(x, y, z);
+                if offset.xz().length_squared() <= 4 {
+                    expected.insert(center + offset);
+                }
+            }
+        }
+    }
+    expected
+}
+
+#[test]
+fn streams_chunks_around_the_camera() {
+    let mut app = app();
+    let loaded = settle(&mut app);
+    assert_eq!(loaded, expected(IVec3::ZERO));
+    let world = app.world().resource::<VoxelWorld>();
+    let indexed = world.octree.chunks_in(IVec3::splat(-9), IVec3::splat(9));
+    assert_eq!(indexed.len(), loaded.len());
+    assert!(loaded.iter().all(|pos| world.lods.contains_key(pos)));
+
+    // Far away, nothing of the first area is left
+    let center = IVec3::new(10, 0, -3);
+    let mut camera = app
+        .world_mut()
+        .query_filtered::<&mut Transform, With<Camera3d>>();
+    camera.single_mut(app.world_mut()).translation = (center * 32).as_vec3();
+    let loaded = settle(&mut app);
+    assert_eq!(loaded, expected(center));
+    let world = app.world().resource::<VoxelWorld>();
+    assert!(world
+        .octree
+        .chunks_in(IVec3::splat(-3), IVec3::splat(3))
+        .is_empty());
+    assert!(world.lods.keys().all(|pos| loaded.contains(pos)));
+}
@@ -0,0 +1,104 @@
+use bevy::prelude::*;
+use bevy_cubes::block::BlockId;
+use bevy_cubes::chunk::Chunk;
+use bevy_cubes::world::{VoxelChanged, VoxelWorld, VoxelWorldPlugin};
+
+fn world_with(chunks: &[IVec3]) -> VoxelWorld {
+    let mut world = VoxelWorld::new();
+    for pos in chunks {
+        world.add_chunk(*pos, Chunk::new(*pos));
+    }
+    world
+}
+
+#[test]
+fn voxels_at_negative_coordinates() {
+    let mut world = world_with(&[IVec3::ZERO, IVec3::NEG_ONE, IVec3::new(-1, 0, 0)]);
+
+    assert_eq!(
+        world.set_voxel(IVec3::NEG_ONE, BlockId::Stone),
+        Some(BlockId::Air)
+    );
+    assert_eq!(world.get_voxel(IVec3::NEG_ONE), Some(BlockId::Stone));
+    assert_eq!(
+        world.chunks[&IVec3::NEG_ONE].data.get(31, 31, 31),
+        BlockId::Stone
+    );
+
+    let pos = IVec3::new(-32, 0, 31);
+    assert_eq!(world.set_voxel(pos, BlockId::Dirt), Some(BlockId::Air));
+    assert_eq!(world.get_voxel(pos), Some(BlockId::Dirt));
+    assert_eq!(
+        world.chunks[&IVec3::new(-1, 0, 0)].data.get(0, 0, 31),
+        BlockId::Dirt
+    );
+
+    // Mirrored positions on the positive side are untouched
+    assert_eq!(world.get_voxel(IVec3::ZERO), Some(BlockId::Air));
+    assert_eq!(world.get_voxel(IVec3::new(31, 0, 31)), Some(BlockId::Air));
+    assert_eq!(world.chunks[&IVec3::ZERO].data.get(1, 1, 1), BlockId::Air);
+
+    // Chunk (-2, 0, 0) isn't loaded
+    assert_eq!(world.get_voxel(IVec3::new(-33, 0, 0)), None);
+    assert_eq!(world.set_voxel(IVec3::new(-33, 0, 0), BlockId::Stone), None);
+}
+
+#[test]
+fn set_voxel_sends_voxel_changed() {
+    let mut app = App::new();
+    app.add_plugins(VoxelWorldPlugin);
+    let pos = IVec3::new(-5, 3, 40);
+    {
+        let mut world = app.world_mut().resource_mut::<VoxelWorld>();
+        world.add_chunk(IVec3::new(-1, 0, 1), Chunk::new(IVec3::new(-1, 0, 1)));
+        world.set_voxel(pos, BlockId::Sand);
+        // Writing the block that's already there changes nothing
+        world.set_voxel(pos, BlockId::Sand);
+    }
+    app.update();
+
+    let events = app.world().resource::<Events<VoxelChanged>>();
+    let changes: Vec<_> = events.get_reader().read(events).copied().collect();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].pos, pos);
+    assert_eq!(changes[0].old, BlockId::Air);
+    assert_eq!(changes[0].new, BlockId::Sand);
+    assert!(app.world().resource::<VoxelWorld>().changes.is_empty());
+}
+
+#[test]
+fn border_edits_dirty_the_neighbour() {
+    // Solid stone so edits don't change any light
+    let mut stone = Chunk::new(IVec3::ZERO);
+    for x in 0..32 {
+        for y in 0..32 {
+            for z in 0..32 {
+                stone.data.set(x, y, z, BlockId::Stone);
+            }
+        }
+    }
+    let mut world = VoxelWorld::new();
+    for pos in [IVec3::new(-1, 0, 0), IVec3::ZERO] {
+        world.add_chunk(
+            pos,
+            Chunk {
+                position: pos,
+                ..stone.clone()
+            },
+        );
+    }
+
+    world.dirty.clear();
+    world.set_voxel(IVec3::new(-16, 5, 5), BlockId::Dirt);
+    assert_eq!(world.dirty, [IVec3::new(-1, 0, 0)].into());
+    assert!(world.unsaved.contains(&IVec3::new(-1, 0, 0)));
+
+    // x = -1 is on the border with chunk 0
+    world.dirty.clear();
+    world.set_voxel(IVec3::new(-1, 5, 5), BlockId::Dirt);
+    assert_eq!(world.dirty, [IVec3::new(-1, 0, 0), IVec3::ZERO].into());
+
+    world.dirty.clear();
+    world.set_voxel(IVec3::new(0, 5, 5), BlockId::Dirt);
+    assert_eq!(world.dirty, [IVec3::new(-1, 0, 0), IVec3::ZERO].into());
+}